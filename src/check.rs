@@ -0,0 +1,1252 @@
+//! Configuration validation for `hyprchoosy config check`.
+//!
+//! This module walks the raw TOML document so every problem can be reported
+//! with its line and column, instead of stopping at the first serde error.
+
+use crate::config::{
+    config_path, misplaced_rule_key, misplaced_rule_message, Config, RESERVED_SECTIONS, WEB_SCHEMES,
+};
+use crate::domain::{is_public_suffix, registrable_domain};
+use crate::matcher::{normalize_host, parse_cidr, ClientPattern};
+use anyhow::{Context, Result};
+use std::env;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use toml_edit::{ImDocument, Item, TableLike};
+
+/// Keys a rule section understands.
+const RULE_KEYS: &[&str] = &[
+    "browser",
+    "clients",
+    "url",
+    "domain",
+    "cidr",
+    "exclude_url",
+    "exclude_clients",
+    "action",
+    "command",
+    "rewrite",
+    "schemes",
+];
+
+/// Window classes and process names of common apps, one list per app, used
+/// to spot `clients` substrings that match more apps than intended.
+const KNOWN_APPS: &[&[&str]] = &[
+    &["code", "code-oss", "code-url-handler"],
+    &["code-insiders"],
+    &["vscodium", "codium"],
+    &["cursor"],
+    &["zed"],
+    &["slack"],
+    &["discord"],
+    &["vesktop"],
+    &["webcord"],
+    &["signal"],
+    &["telegram-desktop", "org.telegram.desktop"],
+    &["element"],
+    &["teams-for-linux"],
+    &["thunderbird"],
+    &["geary"],
+    &["evolution"],
+    &["mailspring"],
+    &["aerc"],
+    &["neomutt"],
+    &["newsboat"],
+    &["kitty"],
+    &["alacritty"],
+    &["foot", "footclient"],
+    &["wezterm", "org.wezfurlong.wezterm"],
+    &["ghostty", "com.mitchellh.ghostty"],
+    &["konsole", "org.kde.konsole"],
+    &["gnome-terminal-server", "org.gnome.terminal"],
+    &["xfce4-terminal"],
+    &["obsidian"],
+    &["logseq"],
+    &["spotify"],
+    &["steam"],
+    &["zoom"],
+    &["org.gnome.nautilus"],
+    &["thunar"],
+];
+
+/// Values `action` accepts.
+const ACTIONS: &[&str] = &["launch", "picker", "copy", "notify", "ignore", "command"];
+
+/// Actions that never open the section's browser.
+const BROWSERLESS_ACTIONS: &[&str] = &["copy", "notify", "ignore", "command"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, self.severity, self.message
+        )
+    }
+}
+
+/// A `clients`, `url` or exclusion entry, remembered for cross-section checks.
+struct Pattern {
+    section: String,
+    value: String,
+    span: Option<Range<usize>>,
+    /// Schemes the section handles; patterns only conflict when these overlap.
+    schemes: Vec<String>,
+}
+
+type Spanned<T> = (T, Option<Range<usize>>);
+
+struct Checker<'a, F> {
+    source: &'a str,
+    browser_exists: F,
+    diagnostics: Vec<Diagnostic>,
+    clients: Vec<Pattern>,
+    urls: Vec<Pattern>,
+    exclude_clients: Vec<Pattern>,
+    exclude_urls: Vec<Pattern>,
+}
+
+impl<'a, F: Fn(&str) -> bool> Checker<'a, F> {
+    fn report(&mut self, severity: Severity, span: Option<Range<usize>>, message: String) {
+        let offset = span.map(|s| s.start).unwrap_or(0);
+        let (line, column) = line_column(self.source, offset);
+        self.diagnostics.push(Diagnostic {
+            severity,
+            line,
+            column,
+            message,
+        });
+    }
+
+    fn check_document(&mut self, doc: &ImDocument<&str>) {
+        let root = doc.as_table();
+
+        if !root.contains_key("default") {
+            self.report(
+                Severity::Error,
+                None,
+                "missing [default] section; links that match no rule have no browser".into(),
+            );
+        }
+
+        for (name, item) in root.iter() {
+            let span = root
+                .get_key_value(name)
+                .and_then(|(key, _)| key.span())
+                .or_else(|| item.span());
+
+            match name {
+                "default" => self.check_default(item, span),
+                _ if self.check_reserved(name, item) => {}
+                "rewrite" => self.check_rewrites("global", item),
+                _ if RESERVED_SECTIONS.contains(&name) => {}
+                _ => match item.as_table_like() {
+                    Some(table) => self.check_rule(name, table, span),
+                    None => self.report(
+                        Severity::Error,
+                        span,
+                        format!("'{}' must be a [{}] section", name, name),
+                    ),
+                },
+            }
+        }
+
+        self.check_duplicates();
+    }
+
+    /// Reports a rule section named like a reserved table; returns whether it was one.
+    fn check_reserved(&mut self, name: &str, item: &Item) -> bool {
+        let Some(table) = item.as_table_like() else {
+            return false;
+        };
+        let Some(key) = misplaced_rule_key(name, table.iter().map(|(key, _)| key)) else {
+            return false;
+        };
+        let span = table.get_key_value(key).and_then(|(key, _)| key.span());
+        self.report(Severity::Error, span, misplaced_rule_message(name, key));
+        true
+    }
+
+    fn check_default(&mut self, item: &Item, span: Option<Range<usize>>) {
+        match item.get("browser") {
+            Some(browser) => self.check_browser("default", browser),
+            None => self.check_browser_exists("firefox", span.clone()),
+        }
+        if let Some(action) = item.get("action") {
+            self.check_action("default", action);
+        }
+        if let Some(table) = item.as_table_like() {
+            self.check_command("default", table, span);
+        }
+    }
+
+    fn check_rule(&mut self, section: &str, table: &dyn TableLike, span: Option<Range<usize>>) {
+        let action = table.get("action").and_then(Item::as_str);
+        let browserless = action.is_some_and(|a| BROWSERLESS_ACTIONS.contains(&a));
+
+        match table.get("browser") {
+            Some(browser) => self.check_browser(section, browser),
+            None if browserless => {}
+            None => self.report(
+                Severity::Error,
+                span.clone(),
+                format!("[{}] has no 'browser'", section),
+            ),
+        }
+
+        self.check_command(section, table, span);
+        let schemes = self.check_schemes(section, table);
+
+        for (key, item) in table.iter() {
+            if !RULE_KEYS.contains(&key) {
+                let span = table.key(key).and_then(|k| k.span());
+                self.report(
+                    Severity::Warning,
+                    span,
+                    format!("unknown key '{}' in [{}]", key, section),
+                );
+            }
+
+            if key == "action" {
+                self.check_action(section, item);
+                continue;
+            }
+            if key == "rewrite" {
+                self.check_rewrites(&format!("[{}]", section), item);
+                continue;
+            }
+            if !matches!(
+                key,
+                "clients" | "url" | "domain" | "cidr" | "exclude_url" | "exclude_clients"
+            ) {
+                continue;
+            }
+            let Some(list) = item.as_array() else {
+                self.report(
+                    Severity::Error,
+                    item.span(),
+                    format!("'{}' in [{}] must be a list of strings", key, section),
+                );
+                continue;
+            };
+
+            if list.is_empty() {
+                self.report(
+                    Severity::Warning,
+                    item.span(),
+                    format!("'{}' in [{}] is empty", key, section),
+                );
+            }
+
+            for value in list.iter() {
+                let Some(pattern) = value.as_str() else {
+                    self.report(
+                        Severity::Error,
+                        value.span(),
+                        format!("'{}' in [{}] must only contain strings", key, section),
+                    );
+                    continue;
+                };
+                let pattern = Pattern {
+                    section: section.to_string(),
+                    value: match key {
+                        "url" | "domain" | "exclude_url"
+                            if unmatchable_host_reason(pattern).is_none() =>
+                        {
+                            normalize_host(pattern)
+                        }
+                        // Lowercasing would change what a regex means (`\D` vs `\d`).
+                        _ if matches!(ClientPattern::parse(pattern), ClientPattern::Regex(_)) => {
+                            pattern.to_string()
+                        }
+                        _ => pattern.to_lowercase(),
+                    },
+                    span: value.span(),
+                    schemes: schemes.clone(),
+                };
+
+                if key == "url" {
+                    if let Some(reason) = unmatchable_host_reason(&pattern.value) {
+                        self.report(
+                            Severity::Error,
+                            pattern.span.clone(),
+                            format!(
+                                "url pattern '{}' can never match: {}",
+                                pattern.value, reason
+                            ),
+                        );
+                    }
+                    self.urls.push(pattern);
+                } else if key == "exclude_url" {
+                    if let Some(reason) = unmatchable_host_reason(&pattern.value) {
+                        self.report(
+                            Severity::Error,
+                            pattern.span.clone(),
+                            format!(
+                                "exclude_url pattern '{}' can never match: {}",
+                                pattern.value, reason
+                            ),
+                        );
+                    }
+                    self.exclude_urls.push(pattern);
+                } else if key == "exclude_clients" {
+                    self.check_client_pattern(section, &pattern);
+                    self.exclude_clients.push(pattern);
+                } else if key == "cidr" {
+                    if parse_cidr(&pattern.value).is_none() {
+                        self.report(
+                            Severity::Error,
+                            pattern.span.clone(),
+                            format!(
+                                "'{}' in [{}] is not an IP address or CIDR range",
+                                pattern.value, section
+                            ),
+                        );
+                    }
+                } else if key == "domain" {
+                    if let Some(reason) = unmatchable_domain_reason(&pattern.value) {
+                        self.report(
+                            Severity::Error,
+                            pattern.span.clone(),
+                            format!("domain '{}' can never match: {}", pattern.value, reason),
+                        );
+                    }
+                } else {
+                    self.check_client_pattern(section, &pattern);
+                    self.clients.push(pattern);
+                }
+            }
+        }
+    }
+
+    /// Reports invalid regexes and substring patterns that match several known apps.
+    fn check_client_pattern(&mut self, section: &str, pattern: &Pattern) {
+        match ClientPattern::parse(&pattern.value) {
+            ClientPattern::Regex(source) => {
+                if let Err(reason) = ClientPattern::regex(source) {
+                    self.report(
+                        Severity::Error,
+                        pattern.span.clone(),
+                        format!(
+                            "'{}' in [{}] is not a valid regex: {}",
+                            pattern.value, section, reason
+                        ),
+                    );
+                }
+            }
+            ClientPattern::Substring(needle) => {
+                // Names of one app, such as `foot` and `footclient`, are fine to catch together.
+                let matched: Vec<&str> = KNOWN_APPS
+                    .iter()
+                    .filter_map(|names| names.iter().copied().find(|n| n.contains(needle)))
+                    .collect();
+                if matched.len() > 1 {
+                    self.report(
+                        Severity::Warning,
+                        pattern.span.clone(),
+                        format!(
+                            "'{}' in [{}] matches any client containing it, such as {}; write '={}' to match it exactly or '^{}' for a prefix",
+                            needle,
+                            section,
+                            matched.join(", "),
+                            needle,
+                            needle
+                        ),
+                    );
+                }
+            }
+            ClientPattern::Exact(_) | ClientPattern::Prefix(_) => {}
+        }
+    }
+
+    /// Validates `schemes` and returns the schemes the section handles.
+    fn check_schemes(&mut self, section: &str, table: &dyn TableLike) -> Vec<String> {
+        let web = || WEB_SCHEMES.iter().map(|s| s.to_string()).collect();
+        let Some(item) = table.get("schemes") else {
+            return web();
+        };
+        let Some(list) = item.as_array() else {
+            self.report(
+                Severity::Error,
+                item.span(),
+                format!("'schemes' in [{}] must be a list of strings", section),
+            );
+            return web();
+        };
+        if list.is_empty() {
+            self.report(
+                Severity::Warning,
+                item.span(),
+                format!("'schemes' in [{}] is empty", section),
+            );
+            return web();
+        }
+
+        let mut schemes = Vec::new();
+        for value in list.iter() {
+            match value.as_str() {
+                Some(scheme) if is_scheme(scheme) => schemes.push(scheme.to_lowercase()),
+                Some(scheme) => self.report(
+                    Severity::Error,
+                    value.span(),
+                    format!(
+                        "'{}' in [{}] is not a scheme; write it without ':' or '//', e.g. \"mailto\"",
+                        scheme, section
+                    ),
+                ),
+                None => self.report(
+                    Severity::Error,
+                    value.span(),
+                    format!("'schemes' in [{}] must only contain strings", section),
+                ),
+            }
+        }
+        schemes
+    }
+
+    fn check_browser(&mut self, section: &str, item: &Item) {
+        match item.as_str() {
+            Some(browser) => self.check_browser_exists(browser, item.span()),
+            None => self.report(
+                Severity::Error,
+                item.span(),
+                format!("'browser' in [{}] must be a string", section),
+            ),
+        }
+    }
+
+    fn check_action(&mut self, section: &str, item: &Item) {
+        match item.as_str() {
+            Some(action) if ACTIONS.contains(&action) => {}
+            Some(action) => self.report(
+                Severity::Error,
+                item.span(),
+                format!(
+                    "unknown action '{}' in [{}], expected one of: {}",
+                    action,
+                    section,
+                    ACTIONS.join(", ")
+                ),
+            ),
+            None => self.report(
+                Severity::Error,
+                item.span(),
+                format!("'action' in [{}] must be a string", section),
+            ),
+        }
+    }
+
+    fn check_command(&mut self, section: &str, table: &dyn TableLike, span: Option<Range<usize>>) {
+        let is_command = table.get("action").and_then(Item::as_str) == Some("command");
+        let Some(item) = table.get("command") else {
+            if is_command {
+                self.report(
+                    Severity::Error,
+                    span,
+                    format!("[{}] has action = \"command\" but no 'command'", section),
+                );
+            }
+            return;
+        };
+
+        if !is_command {
+            self.report(
+                Severity::Warning,
+                item.span(),
+                format!(
+                    "'command' in [{}] is ignored unless action = \"command\"",
+                    section
+                ),
+            );
+        }
+
+        let argv = item
+            .as_array()
+            .and_then(|list| list.iter().map(|v| v.as_str()).collect::<Option<Vec<_>>>());
+        match argv.as_deref() {
+            Some([program, ..]) if !(self.browser_exists)(program) => self.report(
+                Severity::Error,
+                item.span(),
+                format!("command '{}' was not found on PATH", program),
+            ),
+            Some([_, ..]) => {}
+            Some([]) => self.report(
+                Severity::Error,
+                item.span(),
+                format!("'command' in [{}] is empty", section),
+            ),
+            None => self.report(
+                Severity::Error,
+                item.span(),
+                format!(
+                    "'command' in [{}] must be a list of strings, e.g. [\"mpv\", \"{{url}}\"]",
+                    section
+                ),
+            ),
+        }
+    }
+
+    /// Checks a `rewrite` list: `[[rewrite]]` tables or a list of inline tables.
+    fn check_rewrites(&mut self, owner: &str, item: &Item) {
+        let rules: Option<Vec<Spanned<&dyn TableLike>>> = match item.as_array_of_tables() {
+            Some(tables) => Some(
+                tables
+                    .iter()
+                    .map(|t| (t as &dyn TableLike, t.span()))
+                    .collect(),
+            ),
+            None => item.as_array().and_then(|list| {
+                list.iter()
+                    .map(|v| Some((v.as_inline_table()? as &dyn TableLike, v.span())))
+                    .collect()
+            }),
+        };
+        let Some(rules) = rules else {
+            self.report(
+                Severity::Error,
+                item.span(),
+                format!(
+                    "{} 'rewrite' must be [[rewrite]] tables or a list of {{ from = ..., to = ... }}",
+                    owner
+                ),
+            );
+            return;
+        };
+
+        for (rule, span) in rules {
+            for key in ["from", "to"] {
+                match rule.get(key) {
+                    Some(value) if value.as_str().is_some_and(|v| !v.trim().is_empty()) => {}
+                    Some(value) => self.report(
+                        Severity::Error,
+                        value.span(),
+                        format!("'{}' in {} rewrite must be a non-empty string", key, owner),
+                    ),
+                    None => self.report(
+                        Severity::Error,
+                        span.clone(),
+                        format!("{} rewrite has no '{}'", owner, key),
+                    ),
+                }
+            }
+            for (key, _) in rule.iter() {
+                if key != "from" && key != "to" {
+                    let span = rule.key(key).and_then(|k| k.span());
+                    self.report(
+                        Severity::Warning,
+                        span,
+                        format!("unknown key '{}' in {} rewrite", key, owner),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_browser_exists(&mut self, browser: &str, span: Option<Range<usize>>) {
+        if browser.trim().is_empty() {
+            self.report(Severity::Error, span, "browser is empty".into());
+        } else if !(self.browser_exists)(browser) {
+            self.report(
+                Severity::Error,
+                span,
+                format!("browser '{}' was not found on PATH", browser),
+            );
+        }
+    }
+
+    fn check_duplicates(&mut self) {
+        let mut found = Vec::new();
+
+        for (kind, patterns) in [("url", &self.urls), ("clients", &self.clients)] {
+            for (i, p) in patterns.iter().enumerate() {
+                for q in &patterns[..i] {
+                    let same_section = p.section == q.section;
+                    if !p.schemes.iter().any(|s| q.schemes.contains(s)) {
+                        continue;
+                    }
+
+                    if p.value == q.value {
+                        let (severity, message) = if same_section {
+                            (
+                                Severity::Warning,
+                                format!("'{}' is listed twice in [{}]", p.value, p.section),
+                            )
+                        } else {
+                            (
+                                Severity::Error,
+                                format!(
+                                    "'{}' is in both [{}] and [{}]",
+                                    p.value, q.section, p.section
+                                ),
+                            )
+                        };
+                        found.push((severity, p.span.clone(), message));
+                        continue;
+                    }
+
+                    for (narrow, broad) in [(p, q), (q, p)] {
+                        if !shadows(kind, &broad.value, &narrow.value)
+                            || self.excludes(kind, broad, narrow)
+                        {
+                            continue;
+                        }
+                        let message = if same_section {
+                            format!(
+                                "'{}' in [{}] is redundant, '{}' already covers it",
+                                narrow.value, narrow.section, broad.value
+                            )
+                        } else {
+                            format!(
+                                "'{}' in [{}] is shadowed by '{}' in [{}]; which section wins is unpredictable",
+                                narrow.value, narrow.section, broad.value, broad.section
+                            )
+                        };
+                        let finding = (Severity::Warning, narrow.span.clone(), message);
+                        // A broad pattern listed twice would report the same overlap twice.
+                        if !found.contains(&finding) {
+                            found.push(finding);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (severity, span, message) in found {
+            self.report(severity, span, message);
+        }
+    }
+}
+
+/// Whether `scheme` is a valid URL scheme name.
+fn is_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+impl<F> Checker<'_, F> {
+    /// Whether `broad`'s section excludes everything `narrow` matches.
+    fn excludes(&self, kind: &str, broad: &Pattern, narrow: &Pattern) -> bool {
+        let exclusions = match kind {
+            "url" => &self.exclude_urls,
+            _ => &self.exclude_clients,
+        };
+        exclusions.iter().any(|e| {
+            e.section == broad.section
+                && (e.value == narrow.value || shadows(kind, &e.value, &narrow.value))
+        })
+    }
+}
+
+/// Whether everything `narrow` matches is also matched by `broad`.
+fn shadows(kind: &str, broad: &str, narrow: &str) -> bool {
+    if kind == "url" {
+        return narrow.ends_with(&format!(".{}", broad));
+    }
+    use ClientPattern::*;
+    match (ClientPattern::parse(broad), ClientPattern::parse(narrow)) {
+        (Substring(b), Substring(n) | Prefix(n) | Exact(n)) => !b.is_empty() && n.contains(b),
+        (Prefix(b), Prefix(n) | Exact(n)) => n.starts_with(b),
+        (Exact(b), Exact(n)) => b == n,
+        _ => false,
+    }
+}
+
+/// Explains why a `url` pattern can't equal any host `parse_url_host` returns.
+fn unmatchable_host_reason(pattern: &str) -> Option<&'static str> {
+    if pattern.is_empty() {
+        Some("it is empty")
+    } else if pattern.contains("://") {
+        Some("patterns are hostnames, remove the scheme")
+    } else if pattern.contains('/') {
+        Some("patterns are hostnames, paths are not matched")
+    } else if pattern.contains(['?', '#']) {
+        Some("patterns are hostnames, queries and fragments are not matched")
+    } else if pattern.starts_with("*.") {
+        Some("wildcards are not supported, a domain already matches its subdomains")
+    } else if pattern.contains(char::is_whitespace) {
+        Some("it contains whitespace")
+    } else if pattern.parse::<std::net::Ipv6Addr>().is_ok() {
+        None
+    } else if !pattern.starts_with('[') && pattern.contains(':') {
+        Some("ports are not part of the host")
+    } else if url::Host::parse(pattern.strip_suffix('.').unwrap_or(pattern)).is_err() {
+        Some("it is not a valid domain name")
+    } else {
+        None
+    }
+}
+
+/// Explains why a `domain` pattern can't equal any host's registrable domain.
+fn unmatchable_domain_reason(pattern: &str) -> Option<String> {
+    if let Some(reason) = unmatchable_host_reason(pattern) {
+        return Some(reason.to_string());
+    }
+    if is_public_suffix(pattern) {
+        return Some(format!(
+            "it is a public suffix, list a domain under it, e.g. 'name.{}'",
+            pattern
+        ));
+    }
+    match registrable_domain(pattern) {
+        Some(domain) if domain == pattern => None,
+        Some(domain) => Some(format!(
+            "it is not a registrable domain, write '{}' or use 'url'",
+            domain
+        )),
+        None => Some("IP addresses and single-label hosts have no registrable domain".into()),
+    }
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+pub(crate) fn browser_exists(browser: &str) -> bool {
+    if browser.contains('/') {
+        return is_executable(Path::new(browser));
+    }
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| is_executable(&dir.join(browser))))
+        .unwrap_or(false)
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Validates a config file's source, reporting every problem found.
+pub fn check_config(source: &str) -> Vec<Diagnostic> {
+    check_config_with(source, browser_exists)
+}
+
+fn check_config_with(source: &str, browser_exists: impl Fn(&str) -> bool) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        source,
+        browser_exists,
+        diagnostics: Vec::new(),
+        clients: Vec::new(),
+        urls: Vec::new(),
+        exclude_clients: Vec::new(),
+        exclude_urls: Vec::new(),
+    };
+
+    match ImDocument::parse(source) {
+        Ok(doc) => checker.check_document(&doc),
+        Err(e) => {
+            checker.report(Severity::Error, e.span(), e.message().trim().to_string());
+            return checker.diagnostics;
+        }
+    }
+
+    // Catch wrong value types the structural checks skip over.
+    if !checker
+        .diagnostics
+        .iter()
+        .any(|d| d.severity == Severity::Error)
+    {
+        if let Err(e) = toml::from_str::<Config>(source) {
+            checker.report(Severity::Error, e.span(), e.message().trim().to_string());
+        }
+    }
+
+    checker
+        .diagnostics
+        .sort_by_key(|d| (d.line, d.column, d.severity == Severity::Warning));
+    checker.diagnostics
+}
+
+/// Runs `hyprchoosy config check`, printing every problem.
+///
+/// Returns whether the configuration is free of errors, and with `strict` of
+/// warnings too.
+pub fn run(strict: bool) -> Result<bool> {
+    let path = config_path();
+    let source = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read config at {}. Set HYPRCHOOSY_CONFIG to override.",
+            path.display()
+        )
+    })?;
+
+    let diagnostics = check_config(&source);
+    for diagnostic in &diagnostics {
+        println!("{}:{}", path.display(), diagnostic);
+    }
+
+    if diagnostics.is_empty() {
+        println!("{}: OK", path.display());
+    }
+    Ok(diagnostics
+        .iter()
+        .all(|d| d.severity == Severity::Warning && !strict))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<String> {
+        check_config_with(source, |browser| browser != "missing-browser")
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let source = r#"
+[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = ["slack"]
+url = ["company.com"]
+"#;
+        assert_eq!(check(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let diagnostics = check("[default]\nbrowser = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].starts_with("2:11: error:"));
+    }
+
+    #[test]
+    fn test_missing_default_and_browser() {
+        let source = "[work]\nclients = [\"slack\"]\n";
+        assert_eq!(
+            check(source),
+            vec![
+                "1:1: error: missing [default] section; links that match no rule have no browser",
+                "1:2: error: [work] has no 'browser'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_lists_and_unknown_keys() {
+        let source = r#"[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = []
+urls = ["company.com"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "6:11: warning: 'clients' in [work] is empty",
+                "7:1: warning: unknown key 'urls' in [work]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unmatchable_url_patterns() {
+        let source = r#"[default]
+browser = "firefox"
+
+[dev]
+browser = "chromium"
+url = ["https://github.com", "gitlab.com/foo", "*.example.com", "localhost:3000", "[::1]"]
+"#;
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 4, "{:?}", diagnostics);
+        assert!(diagnostics[0].starts_with("6:8: error: url pattern 'https://github.com'"));
+        assert!(diagnostics[1]
+            .contains("'gitlab.com/foo' can never match: patterns are hostnames, paths"));
+        assert!(diagnostics[2].contains("wildcards are not supported"));
+        assert!(diagnostics[3].contains("ports are not part of the host"));
+    }
+
+    #[test]
+    fn test_duplicates_and_shadowing() {
+        let source = r#"[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = ["slack"]
+url = ["github.com", "company.com", "company.com"]
+
+[dev]
+browser = "chromium"
+clients = ["slack-desktop"]
+url = ["github.com", "api.company.com"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "7:37: warning: 'company.com' is listed twice in [work]",
+                "11:12: warning: 'slack-desktop' in [dev] is shadowed by 'slack' in [work]; which section wins is unpredictable",
+                "12:8: error: 'github.com' is in both [work] and [dev]",
+                "12:22: warning: 'api.company.com' in [dev] is shadowed by 'company.com' in [work]; which section wins is unpredictable",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_browser_executable() {
+        let source = r#"[default]
+browser = "missing-browser"
+
+[work]
+browser = "chrome"
+"#;
+        assert_eq!(
+            check(source),
+            vec!["2:11: error: browser 'missing-browser' was not found on PATH"]
+        );
+    }
+
+    #[test]
+    fn test_wrong_value_type() {
+        let source = r#"[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = "slack"
+"#;
+        assert_eq!(
+            check(source),
+            vec!["6:11: error: 'clients' in [work] must be a list of strings"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_action() {
+        let source = r#"[default]
+browser = "firefox"
+action = "picker"
+
+[picker]
+command = "fuzzel --dmenu"
+
+[work]
+browser = "chrome"
+action = "open"
+"#;
+        assert_eq!(
+            check(source),
+            vec!["10:10: error: unknown action 'open' in [work], expected one of: launch, picker, copy, notify, ignore, command"]
+        );
+    }
+
+    #[test]
+    fn test_browserless_actions() {
+        let source = r#"[default]
+browser = "firefox"
+
+[tracking]
+clients = ["mailchimp"]
+action = "notify"
+
+[work]
+url = ["company.com"]
+action = "picker"
+"#;
+        assert_eq!(check(source), vec!["8:2: error: [work] has no 'browser'"]);
+    }
+
+    #[test]
+    fn test_normalized_url_patterns() {
+        let source = r#"[default]
+browser = "firefox"
+
+[de]
+browser = "firefox"
+url = ["münchen.de", "example.com.", "bad%host.com"]
+
+[other]
+browser = "firefox"
+url = ["xn--mnchen-3ya.de", "EXAMPLE.com"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "6:38: error: url pattern 'bad%host.com' can never match: it is not a valid domain name",
+                "10:8: error: 'xn--mnchen-3ya.de' is in both [de] and [other]",
+                "10:29: error: 'example.com' is in both [de] and [other]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_domain_patterns() {
+        let source = r#"[default]
+browser = "firefox"
+
+[pages]
+browser = "firefox"
+domain = ["alice.github.io", "github.io", "www.example.co.uk", "10.0.0.1", "example.com/x"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "6:30: error: domain 'github.io' can never match: it is a public suffix, list a domain under it, e.g. 'name.github.io'",
+                "6:43: error: domain 'www.example.co.uk' can never match: it is not a registrable domain, write 'example.co.uk' or use 'url'",
+                "6:64: error: domain '10.0.0.1' can never match: IP addresses and single-label hosts have no registrable domain",
+                "6:76: error: domain 'example.com/x' can never match: patterns are hostnames, paths are not matched",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cidr() {
+        let source = r#"[default]
+browser = "firefox"
+
+[internal]
+browser = "firefox"
+cidr = ["10.0.0.0/8", "::1/128", "192.168.1.1", "10.0.0.0/33", "intranet"]
+url = ["::1", "[fd00::1]", "localhost:8080"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "6:49: error: '10.0.0.0/33' in [internal] is not an IP address or CIDR range",
+                "6:64: error: 'intranet' in [internal] is not an IP address or CIDR range",
+                "7:28: error: url pattern 'localhost:8080' can never match: ports are not part of the host",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_client_patterns_of_one_app() {
+        let source = r#"[default]
+browser = "firefox"
+
+[chat]
+browser = "chromium"
+clients = ["discord", "telegram", "foot", "ghostty"]
+
+[terminals]
+browser = "chromium"
+clients = ["term"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "10:12: warning: 'term' in [terminals] matches any client containing it, such as wezterm, gnome-terminal-server, xfce4-terminal; write '=term' to match it exactly or '^term' for a prefix",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let source = r#"[default]
+browser = "firefox"
+
+[google]
+browser = "chrome"
+url = ["google.com"]
+clients = ["code"]
+exclude_url = ["mail.google.com", "http://x.com"]
+exclude_clients = ["vscode"]
+
+[mail]
+browser = "thunderbird"
+url = ["mail.google.com", "inbox.google.com"]
+clients = ["vscode-insiders"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "7:12: warning: 'code' in [google] matches any client containing it, such as code, code-insiders; write '=code' to match it exactly or '^code' for a prefix",
+                "8:35: error: exclude_url pattern 'http://x.com' can never match: patterns are hostnames, remove the scheme",
+                "13:27: warning: 'inbox.google.com' in [mail] is shadowed by 'google.com' in [google]; which section wins is unpredictable",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_client_match_modes() {
+        let source = r#"[default]
+browser = "firefox"
+
+[editor]
+browser = "chromium"
+clients = ["=code", "^vscod", "/^(Zed|cursor)$/", "/[/"]
+
+[insiders]
+browser = "chrome"
+clients = ["=vscodium", "code-oss", "zed"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "6:51: error: '/[/' in [editor] is not a valid regex: unclosed character class",
+                "10:12: warning: '=vscodium' in [insiders] is shadowed by '^vscod' in [editor]; which section wins is unpredictable",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rule_section_named_like_reserved_table() {
+        let source = r#"[default]
+browser = "firefox"
+
+[matching]
+browser = "chromium"
+url = ["example.com"]
+"#;
+        assert_eq!(
+            check(source),
+            vec!["5:1: error: [matching] is reserved for hyprchoosy settings, but has the rule key 'browser'; rename the section, e.g. to [matching-links]"]
+        );
+    }
+
+    #[test]
+    fn test_reserved_tables_are_not_rules() {
+        let source = r#"[default]
+browser = "firefox"
+
+[privacy]
+strip_params = true
+
+[unwrap]
+enabled = false
+"#;
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn test_command_action() {
+        let source = r#"[default]
+browser = "firefox"
+
+[video]
+url = ["youtube.com"]
+action = "command"
+command = ["mpv", "{url}"]
+
+[zoom]
+url = ["zoom.us"]
+action = "command"
+
+[code]
+url = ["vscode.dev"]
+command = "code --open-url {url}"
+
+[meet]
+url = ["meet.google.com"]
+action = "command"
+command = ["missing-browser", "{url}"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "9:2: error: [zoom] has action = \"command\" but no 'command'",
+                "13:2: error: [code] has no 'browser'",
+                "15:11: error: 'command' in [code] must be a list of strings, e.g. [\"mpv\", \"{url}\"]",
+                "15:11: warning: 'command' in [code] is ignored unless action = \"command\"",
+                "20:11: error: command 'missing-browser' was not found on PATH",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rewrites() {
+        let source = r#"[default]
+browser = "firefox"
+
+[[rewrite]]
+from = "twitter.com"
+to = "xcancel.com"
+
+[[rewrite]]
+from = "www.reddit.com"
+too = "old.reddit.com"
+
+[social]
+browser = "brave"
+rewrite = [{ from = "", to = "nitter.net" }]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "8:1: error: global rewrite has no 'to'",
+                "10:1: warning: unknown key 'too' in global rewrite",
+                "14:21: error: 'from' in [social] rewrite must be a non-empty string",
+            ]
+        );
+
+        let source = "[default]\nbrowser = \"firefox\"\n\n[rewrite]\nfrom = \"a.com\"\n";
+        assert_eq!(
+            check(source),
+            vec!["4:1: error: global 'rewrite' must be [[rewrite]] tables or a list of { from = ..., to = ... }"]
+        );
+    }
+
+    #[test]
+    fn test_schemes() {
+        let source = r#"[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = ["slack"]
+
+[work-mail]
+browser = "thunderbird"
+clients = ["slack"]
+schemes = ["mailto", "mailto:", 3]
+
+[chat]
+browser = "chromium"
+clients = ["slack"]
+schemes = ["https"]
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                "11:22: error: 'mailto:' in [work-mail] is not a scheme; write it without ':' or '//', e.g. \"mailto\"",
+                "11:33: error: 'schemes' in [work-mail] must only contain strings",
+                "15:12: error: 'slack' is in both [work] and [chat]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_column() {
+        assert_eq!(line_column("abc\ndef", 0), (1, 1));
+        assert_eq!(line_column("abc\ndef", 5), (2, 2));
+        assert_eq!(line_column("é\nx", 3), (2, 1));
+    }
+}
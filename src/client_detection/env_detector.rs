@@ -1,32 +1,194 @@
+use super::process_tree_detector::SKIP_LIST;
 use super::ClientDetector;
 use std::env;
 
 #[cfg(feature = "debug")]
 use log::{debug, info};
 
+/// Environment variables that name a desktop file, in priority order.
+///
+/// 1. `GIO_LAUNCHED_DESKTOP_FILE` - set by GLib when launching from a `.desktop` file
+/// 2. `BAMF_DESKTOP_FILE_HINT` - set by BAMF-aware launchers (Unity, Plank, ...)
+/// 3. `CHROME_DESKTOP` - set by Chromium and Electron apps and inherited by their children
+const DESKTOP_FILE_VARS: &[&str] = &[
+    "GIO_LAUNCHED_DESKTOP_FILE",
+    "BAMF_DESKTOP_FILE_HINT",
+    "CHROME_DESKTOP",
+];
+
+/// Startup-notification variables, consulted after [`DESKTOP_FILE_VARS`].
+///
+/// 4. `DESKTOP_STARTUP_ID` - X11 startup notification id
+/// 5. `XDG_ACTIVATION_TOKEN` - Wayland activation token
+///
+/// Both only identify the client when they follow the `launcher/launchee/...`
+/// layout libstartup-notification uses. GLib ids and other opaque tokens are
+/// ignored.
+const STARTUP_ID_VARS: &[&str] = &["DESKTOP_STARTUP_ID", "XDG_ACTIVATION_TOKEN"];
+
 pub struct EnvDetector;
 
 impl ClientDetector for EnvDetector {
     fn detect(&self) -> Option<String> {
-        if let Ok(desktop_file) = env::var("GIO_LAUNCHED_DESKTOP_FILE") {
+        detect_from(|name| env::var(name).ok())
+    }
+}
+
+fn detect_from(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    for name in DESKTOP_FILE_VARS {
+        let Some(value) = var(name) else { continue };
+
+        #[cfg(feature = "debug")]
+        debug!("Found {}: {}", name, value);
+
+        if let Some(app_name) = app_from_desktop_file(&value) {
             #[cfg(feature = "debug")]
-            debug!("Found GIO_LAUNCHED_DESKTOP_FILE: {}", desktop_file);
+            info!("Detected client from env ({}): '{}'", name, app_name);
+            return Some(app_name);
+        }
+    }
 
-            if let Some(filename) = desktop_file.rsplit('/').next() {
-                let app_name = filename.trim_end_matches(".desktop");
+    for name in STARTUP_ID_VARS {
+        let Some(value) = var(name) else { continue };
 
-                if app_name == "hyprchoosy" {
-                    #[cfg(feature = "debug")]
-                    debug!("Skipping hyprchoosy.desktop (looking for originating app)");
-                    return None;
-                }
+        #[cfg(feature = "debug")]
+        debug!("Found {}: {}", name, value);
 
-                #[cfg(feature = "debug")]
-                info!("Detected client from env: '{}'", app_name);
-                return Some(app_name.to_string());
-            }
+        if let Some(app_name) = app_from_startup_id(&value) {
+            #[cfg(feature = "debug")]
+            info!("Detected client from env ({}): '{}'", name, app_name);
+            return Some(app_name);
         }
+    }
+
+    None
+}
+
+fn app_from_desktop_file(desktop_file: &str) -> Option<String> {
+    let filename = desktop_file.rsplit('/').next()?;
+    let app_name = filename.trim_end_matches(".desktop");
+
+    if app_name.is_empty() || app_name == "hyprchoosy" {
+        #[cfg(feature = "debug")]
+        debug!("Skipping '{}' (looking for originating app)", filename);
+        return None;
+    }
+
+    Some(app_name.to_string())
+}
+
+/// Extracts the launcher from a `launcher/launchee/pid-seq-host_TIMEts` startup id.
+///
+/// The launcher is the program that asked for hyprchoosy to be started, which
+/// is the app the link was clicked in.
+fn app_from_startup_id(id: &str) -> Option<String> {
+    let mut parts = id.split('/');
+    let launcher = parts.next()?;
+    let _launchee = parts.next()?;
+    parts.next()?;
+
+    let name = launcher.to_lowercase();
+    let name = name.trim_end_matches(".desktop");
+
+    if name.is_empty() || name == "hyprchoosy" || SKIP_LIST.iter().any(|s| name.contains(s)) {
+        #[cfg(feature = "debug")]
+        debug!("Skipping startup id launcher '{}'", name);
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect_with(vars: &[(&str, &str)]) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        detect_from(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_gio_desktop_file() {
+        assert_eq!(
+            detect_with(&[(
+                "GIO_LAUNCHED_DESKTOP_FILE",
+                "/usr/share/applications/slack.desktop"
+            )]),
+            Some("slack".to_string())
+        );
+    }
+
+    #[test]
+    fn test_skips_own_desktop_file() {
+        assert_eq!(
+            detect_with(&[(
+                "GIO_LAUNCHED_DESKTOP_FILE",
+                "/home/user/.local/share/applications/hyprchoosy.desktop"
+            )]),
+            None
+        );
+        assert_eq!(
+            detect_with(&[
+                ("GIO_LAUNCHED_DESKTOP_FILE", "hyprchoosy.desktop"),
+                ("CHROME_DESKTOP", "discord.desktop"),
+            ]),
+            Some("discord".to_string())
+        );
+    }
+
+    #[test]
+    fn test_priority_order() {
+        assert_eq!(
+            detect_with(&[
+                (
+                    "XDG_ACTIVATION_TOKEN",
+                    "thunderbird/hyprchoosy/1-0-host_TIME1"
+                ),
+                (
+                    "BAMF_DESKTOP_FILE_HINT",
+                    "/usr/share/applications/zoom.desktop"
+                ),
+                ("CHROME_DESKTOP", "slack.desktop"),
+            ]),
+            Some("zoom".to_string())
+        );
+        assert_eq!(
+            detect_with(&[
+                (
+                    "XDG_ACTIVATION_TOKEN",
+                    "thunderbird/hyprchoosy/1-0-host_TIME1"
+                ),
+                ("DESKTOP_STARTUP_ID", "evolution/hyprchoosy/1-0-host_TIME1"),
+            ]),
+            Some("evolution".to_string())
+        );
+    }
+
+    #[test]
+    fn test_startup_id_launcher() {
+        assert_eq!(
+            app_from_startup_id("thunderbird/hyprchoosy/4242-0-laptop_TIME123456"),
+            Some("thunderbird".to_string())
+        );
+        assert_eq!(
+            app_from_startup_id("Evolution/hyprchoosy.desktop/1-2-host_TIME9"),
+            Some("evolution".to_string())
+        );
+    }
 
-        None
+    #[test]
+    fn test_startup_id_ignored() {
+        assert_eq!(app_from_startup_id("8f2c1b4e-5a3d-4c1e-9b7a"), None);
+        assert_eq!(app_from_startup_id("gio/hyprchoosy/1-0-host_TIME1"), None);
+        assert_eq!(
+            app_from_startup_id("xdg-open/hyprchoosy/1-0-host_TIME1"),
+            None
+        );
+        assert_eq!(app_from_startup_id(""), None);
     }
 }
@@ -0,0 +1,151 @@
+use super::process_tree_detector::ancestor_pids;
+use super::ClientDetector;
+use crate::config::runtime_dir;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "debug")]
+use log::{debug, info};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusEntry {
+    pub class: String,
+    pub pid: u32,
+}
+
+/// Recently focused windows, most recent first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FocusHistory {
+    entries: VecDeque<FocusEntry>,
+}
+
+impl FocusHistory {
+    /// Moves `entry` to the front, keeping at most `capacity` windows.
+    pub fn record(&mut self, entry: FocusEntry, capacity: usize) {
+        self.entries.retain(|e| *e != entry);
+        self.entries.push_front(entry);
+        self.entries.truncate(capacity);
+    }
+
+    /// Finds the most recently focused window whose process is one of `ancestors`.
+    pub fn owner_of(&self, ancestors: &[u32]) -> Option<&FocusEntry> {
+        self.entries.iter().find(|e| ancestors.contains(&e.pid))
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec(self)?;
+        fs::write(path, data)
+            .with_context(|| format!("Failed to write focus history to {}", path.display()))
+    }
+}
+
+/// State file the daemon mirrors its focus history to.
+pub fn focus_history_path() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join("hyprchoosy-focus.json"))
+}
+
+/// Finds the client among recently focused windows that owns the calling process.
+///
+/// Unlike the active window, this still finds the right app when the link was
+/// opened from the background, e.g. from a notification or another workspace.
+pub struct FocusHistoryDetector {
+    history: FocusHistory,
+    pid: u32,
+}
+
+impl FocusHistoryDetector {
+    pub fn new(history: FocusHistory, pid: u32) -> Self {
+        Self { history, pid }
+    }
+
+    /// Uses the daemon's state file, if there is one, for the current process.
+    pub fn from_state_file() -> Option<Self> {
+        let history = FocusHistory::load(&focus_history_path().ok()?)?;
+        Some(Self::new(history, std::process::id()))
+    }
+}
+
+impl ClientDetector for FocusHistoryDetector {
+    fn detect(&self) -> Option<String> {
+        #[cfg(feature = "debug")]
+        debug!("Attempting to detect client from focus history...");
+
+        let owner = self.history.owner_of(&ancestor_pids(self.pid))?;
+
+        #[cfg(feature = "debug")]
+        info!(
+            "Detected client from focus history: '{}' (PID {})",
+            owner.class, owner.pid
+        );
+        Some(owner.class.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(class: &str, pid: u32) -> FocusEntry {
+        FocusEntry {
+            class: class.to_string(),
+            pid,
+        }
+    }
+
+    #[test]
+    fn test_record_moves_to_front() {
+        let mut history = FocusHistory::default();
+        history.record(entry("slack", 10), 8);
+        history.record(entry("firefox", 20), 8);
+        history.record(entry("slack", 10), 8);
+
+        assert_eq!(
+            history.entries,
+            VecDeque::from([entry("slack", 10), entry("firefox", 20)])
+        );
+    }
+
+    #[test]
+    fn test_record_capacity() {
+        let mut history = FocusHistory::default();
+        for pid in 0..5 {
+            history.record(entry("foot", pid), 3);
+        }
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(history.entries[0], entry("foot", 4));
+    }
+
+    #[test]
+    fn test_owner_of_prefers_ancestor_over_recent_focus() {
+        let mut history = FocusHistory::default();
+        history.record(entry("thunderbird", 30), 8);
+        history.record(entry("slack", 10), 8);
+        history.record(entry("firefox", 20), 8);
+
+        let owner = history.owner_of(&[99, 30, 1]).unwrap();
+        assert_eq!(owner.class, "thunderbird");
+        assert!(history.owner_of(&[99, 1]).is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("focus.json");
+
+        let mut history = FocusHistory::default();
+        history.record(entry("slack", 10), 8);
+        history.save(&path).unwrap();
+
+        let loaded = FocusHistory::load(&path).unwrap();
+        assert_eq!(loaded.entries, history.entries);
+        assert!(FocusHistory::load(&dir.path().join("missing.json")).is_none());
+    }
+}
@@ -1,4 +1,6 @@
+use super::process_tree_detector::ancestor_pids;
 use super::ClientDetector;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use std::process::Command;
 
@@ -6,32 +8,91 @@ use std::process::Command;
 use log::{debug, info};
 
 #[derive(Deserialize)]
-struct HyprlandWindow {
-    class: String,
+pub(crate) struct HyprlandWindow {
+    pub class: String,
+    #[serde(default)]
+    pub pid: i64,
 }
 
-pub struct HyprlandDetector;
+impl HyprlandWindow {
+    pub fn client_name(&self) -> Option<String> {
+        let class = self.class.to_lowercase();
+        (!class.is_empty() && class != "unknown").then_some(class)
+    }
+}
+
+fn hyprctl<T: DeserializeOwned>(args: &[&str]) -> Option<T> {
+    let output = Command::new("hyprctl").args(args).output().ok()?;
+
+    if !output.status.success() {
+        #[cfg(feature = "debug")]
+        debug!("hyprctl {:?} failed", args);
+        return None;
+    }
+
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Queries the active window through `hyprctl`.
+pub(crate) fn active_window() -> Option<HyprlandWindow> {
+    hyprctl(&["activewindow", "-j"])
+}
+
+/// Finds the window owned by the nearest of `ancestors`.
+///
+/// This is the app that actually spawned the caller, even if focus has moved
+/// elsewhere between the click and the launch.
+pub(crate) fn owning_client(ancestors: &[u32]) -> Option<String> {
+    let windows: Vec<HyprlandWindow> = hyprctl(&["clients", "-j"])?;
+    owning_window(&windows, ancestors)?.client_name()
+}
+
+fn owning_window<'a>(
+    windows: &'a [HyprlandWindow],
+    ancestors: &[u32],
+) -> Option<&'a HyprlandWindow> {
+    ancestors
+        .iter()
+        .find_map(|&pid| windows.iter().find(|w| w.pid == i64::from(pid)))
+}
+
+pub struct HyprlandDetector {
+    pid: u32,
+}
+
+impl HyprlandDetector {
+    /// Cross-checks windows against the current process's ancestors.
+    pub fn new() -> Self {
+        Self::from_pid(std::process::id())
+    }
+
+    /// Cross-checks windows against the ancestors of `pid`.
+    pub fn from_pid(pid: u32) -> Self {
+        Self { pid }
+    }
+}
+
+impl Default for HyprlandDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl ClientDetector for HyprlandDetector {
     fn detect(&self) -> Option<String> {
         #[cfg(feature = "debug")]
-        debug!("Attempting to detect client from Hyprland active window...");
+        debug!("Attempting to detect client from Hyprland windows...");
 
-        let output = Command::new("hyprctl")
-            .args(["activewindow", "-j"])
-            .output()
-            .ok()?;
-
-        if !output.status.success() {
+        if let Some(class) = owning_client(&ancestor_pids(self.pid)) {
             #[cfg(feature = "debug")]
-            debug!("hyprctl command failed");
-            return None;
+            info!("Detected client from window owning the caller: '{}'", class);
+            return Some(class);
         }
 
-        let window: HyprlandWindow = serde_json::from_slice(&output.stdout).ok()?;
-        let class = window.class.to_lowercase();
+        #[cfg(feature = "debug")]
+        debug!("No window owns the caller, falling back to the active window");
 
-        if !class.is_empty() && class != "unknown" {
+        if let Some(class) = active_window()?.client_name() {
             #[cfg(feature = "debug")]
             info!("Detected client from Hyprland window: '{}'", class);
             return Some(class);
@@ -42,3 +103,43 @@ impl ClientDetector for HyprlandDetector {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows() -> Vec<HyprlandWindow> {
+        serde_json::from_str(
+            r#"[
+                {"address": "0x1", "class": "firefox", "pid": 100},
+                {"address": "0x2", "class": "Slack", "pid": 200},
+                {"address": "0x3", "class": "foot", "pid": 300}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_owning_window_nearest_ancestor() {
+        let windows = windows();
+        let owner = owning_window(&windows, &[250, 200, 300, 1]).unwrap();
+        assert_eq!(owner.client_name(), Some("slack".to_string()));
+    }
+
+    #[test]
+    fn test_owning_window_none() {
+        let windows = windows();
+        assert!(owning_window(&windows, &[250, 1]).is_none());
+        assert!(owning_window(&windows, &[]).is_none());
+    }
+
+    #[test]
+    fn test_client_name() {
+        let window: HyprlandWindow =
+            serde_json::from_str(r#"{"class": "Unknown", "pid": 1}"#).unwrap();
+        assert_eq!(window.client_name(), None);
+
+        let window: HyprlandWindow = serde_json::from_str(r#"{"class": ""}"#).unwrap();
+        assert_eq!(window.client_name(), None);
+    }
+}
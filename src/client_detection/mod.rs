@@ -4,12 +4,25 @@
 //! initiated a URL open request, using a chain-of-responsibility pattern.
 
 mod env_detector;
+mod focus_history;
 mod hyprland_detector;
+mod niri_detector;
 mod process_tree_detector;
+mod sway_detector;
 
 pub use env_detector::EnvDetector;
+pub use focus_history::{focus_history_path, FocusEntry, FocusHistory, FocusHistoryDetector};
 pub use hyprland_detector::HyprlandDetector;
+pub use niri_detector::NiriDetector;
 pub use process_tree_detector::ProcessTreeDetector;
+pub use sway_detector::SwayDetector;
+
+pub(crate) use hyprland_detector::{active_window, owning_client};
+pub(crate) use process_tree_detector::ancestor_pids;
+
+use crate::config::Config;
+use crate::daemon;
+use std::env;
 
 #[cfg(feature = "debug")]
 use log::{info, warn};
@@ -18,15 +31,48 @@ pub trait ClientDetector {
     fn detect(&self) -> Option<String>;
 }
 
-pub fn detect_client() -> Option<String> {
+/// Picks the active-window detector for the running compositor.
+///
+/// `pid` is the process the URL came from, used where the compositor can tell
+/// which window owns it.
+pub fn window_detector(pid: u32) -> Option<Box<dyn ClientDetector>> {
+    if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        #[cfg(feature = "debug")]
+        info!("Using Hyprland window detector");
+        return Some(Box::new(HyprlandDetector::from_pid(pid)));
+    }
+
+    if let Some(socket) = env::var_os("SWAYSOCK") {
+        #[cfg(feature = "debug")]
+        info!("Using sway window detector");
+        return Some(Box::new(SwayDetector::new(socket)));
+    }
+
+    if let Some(socket) = env::var_os("NIRI_SOCKET") {
+        #[cfg(feature = "debug")]
+        info!("Using niri window detector");
+        return Some(Box::new(NiriDetector::new(socket)));
+    }
+
+    #[cfg(feature = "debug")]
+    warn!("No supported compositor detected, skipping active window detection");
+    None
+}
+
+pub fn detect_client(cfg: &Config) -> Option<String> {
     #[cfg(feature = "debug")]
     info!("Starting client detection...");
 
-    let detectors: Vec<Box<dyn ClientDetector>> = vec![
-        Box::new(HyprlandDetector),
-        Box::new(EnvDetector),
-        Box::new(ProcessTreeDetector),
-    ];
+    let mut detectors: Vec<Box<dyn ClientDetector>> = Vec::new();
+    // The state file is only current while the daemon that writes it is up.
+    if cfg.focus_history.enabled && daemon::is_running() {
+        if let Some(history) = FocusHistoryDetector::from_state_file() {
+            detectors.push(Box::new(history));
+        }
+    }
+    detectors.extend(window_detector(std::process::id()));
+    detectors.push(Box::new(EnvDetector));
+    detectors.push(Box::new(ProcessTreeDetector::new()));
 
     for detector in detectors {
         if let Some(client) = detector.detect() {
@@ -0,0 +1,138 @@
+use super::ClientDetector;
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(feature = "debug")]
+use log::{debug, info};
+
+const IPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+enum NiriReply {
+    Ok(NiriResponse),
+    Err(String),
+}
+
+#[derive(Deserialize)]
+enum NiriResponse {
+    FocusedWindow(Option<NiriWindow>),
+}
+
+#[derive(Deserialize)]
+struct NiriWindow {
+    app_id: Option<String>,
+}
+
+impl NiriWindow {
+    /// The window's `app_id`. Titles are page or document names, like
+    /// "Slack – GitHub PR", so they are never used as a client name.
+    fn client_name(&self) -> Option<String> {
+        let app_id = self.app_id.as_ref()?.to_lowercase();
+        (!app_id.is_empty()).then_some(app_id)
+    }
+}
+
+/// Reads the focused window from niri's JSON IPC socket.
+pub struct NiriDetector {
+    socket: PathBuf,
+}
+
+impl NiriDetector {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    fn focused_window(&self) -> std::io::Result<String> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+        stream.set_write_timeout(Some(IPC_TIMEOUT))?;
+
+        stream.write_all(b"\"FocusedWindow\"\n")?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(line)
+    }
+}
+
+impl ClientDetector for NiriDetector {
+    fn detect(&self) -> Option<String> {
+        #[cfg(feature = "debug")]
+        debug!(
+            "Attempting to detect client from niri IPC at {}...",
+            self.socket.display()
+        );
+
+        let reply = self.focused_window().ok()?;
+        let window = match serde_json::from_str(&reply).ok()? {
+            NiriReply::Ok(NiriResponse::FocusedWindow(window)) => window?,
+            NiriReply::Err(_e) => {
+                #[cfg(feature = "debug")]
+                debug!("niri returned an error: {}", _e);
+                return None;
+            }
+        };
+
+        #[cfg(feature = "debug")]
+        debug!("niri focused window: app_id={:?}", window.app_id);
+
+        let name = window.client_name()?;
+
+        #[cfg(feature = "debug")]
+        info!("Detected client from niri focused window: '{}'", name);
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    fn detect_with_reply(reply: &'static str) -> Option<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("niri.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            (&stream).write_all(reply.as_bytes()).unwrap();
+            request
+        });
+
+        let client = NiriDetector::new(&socket).detect();
+        assert_eq!(server.join().unwrap(), "\"FocusedWindow\"\n");
+        client
+    }
+
+    #[test]
+    fn test_focused_app_id() {
+        let reply = r#"{"Ok":{"FocusedWindow":{"id":12,"title":"general - Slack","app_id":"Slack","pid":4242,"workspace_id":1,"is_focused":true}}}"#;
+        assert_eq!(detect_with_reply(reply), Some("slack".to_string()));
+    }
+
+    #[test]
+    fn test_ignores_title() {
+        let reply =
+            r#"{"Ok":{"FocusedWindow":{"id":3,"title":"Slack – GitHub PR","app_id":null}}}"#;
+        assert_eq!(detect_with_reply(reply), None);
+    }
+
+    #[test]
+    fn test_no_focused_window() {
+        assert_eq!(detect_with_reply(r#"{"Ok":{"FocusedWindow":null}}"#), None);
+    }
+
+    #[test]
+    fn test_error_reply() {
+        assert_eq!(detect_with_reply(r#"{"Err":"unknown request"}"#), None);
+    }
+}
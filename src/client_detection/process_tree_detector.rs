@@ -4,7 +4,7 @@ use sysinfo::{Pid, System};
 #[cfg(feature = "debug")]
 use log::{debug, info, warn};
 
-const SKIP_LIST: &[&str] = &[
+pub(crate) const SKIP_LIST: &[&str] = &[
     "xdg-open",
     "gio",
     "systemd",
@@ -21,7 +21,27 @@ const SKIP_LIST: &[&str] = &[
 
 const MAX_STEPS: usize = 16;
 
-pub struct ProcessTreeDetector;
+pub struct ProcessTreeDetector {
+    pid: u32,
+}
+
+impl ProcessTreeDetector {
+    /// Walks up from the current process.
+    pub fn new() -> Self {
+        Self::from_pid(std::process::id())
+    }
+
+    /// Walks up from `pid`, e.g. a shim that forwarded its request to the daemon.
+    pub fn from_pid(pid: u32) -> Self {
+        Self { pid }
+    }
+}
+
+impl Default for ProcessTreeDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl ClientDetector for ProcessTreeDetector {
     fn detect(&self) -> Option<String> {
@@ -31,11 +51,11 @@ impl ClientDetector for ProcessTreeDetector {
         let mut sys = System::new_all();
         sys.refresh_processes();
 
-        let mut pid = Pid::from_u32(std::process::id());
+        let mut pid = Pid::from_u32(self.pid);
         let mut steps = 0;
 
         #[cfg(feature = "debug")]
-        debug!("Current PID: {}", pid);
+        debug!("Starting PID: {}", pid);
 
         while steps < MAX_STEPS {
             let proc = sys.process(pid)?;
@@ -77,3 +97,23 @@ impl ClientDetector for ProcessTreeDetector {
         None
     }
 }
+
+/// Returns the PIDs above `pid`, nearest parent first.
+pub(crate) fn ancestor_pids(pid: u32) -> Vec<u32> {
+    let mut sys = System::new();
+    sys.refresh_processes();
+
+    let mut ancestors = Vec::new();
+    let mut pid = Pid::from_u32(pid);
+    while ancestors.len() < MAX_STEPS {
+        let Some(ppid) = sys.process(pid).and_then(|p| p.parent()) else {
+            break;
+        };
+        ancestors.push(ppid.as_u32());
+        pid = ppid;
+    }
+
+    #[cfg(feature = "debug")]
+    debug!("Ancestor PIDs: {:?}", ancestors);
+    ancestors
+}
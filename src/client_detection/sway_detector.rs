@@ -0,0 +1,194 @@
+use super::ClientDetector;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(feature = "debug")]
+use log::{debug, info};
+
+const IPC_MAGIC: &[u8; 6] = b"i3-ipc";
+const IPC_GET_TREE: u32 = 4;
+const IPC_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+struct SwayNode {
+    #[serde(default)]
+    focused: bool,
+    app_id: Option<String>,
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    class: Option<String>,
+}
+
+impl SwayNode {
+    fn find_focused(&self) -> Option<&SwayNode> {
+        if self.focused {
+            return Some(self);
+        }
+        self.nodes
+            .iter()
+            .chain(&self.floating_nodes)
+            .find_map(SwayNode::find_focused)
+    }
+
+    /// Wayland-native windows report `app_id`; XWayland windows only have a class.
+    fn client_name(&self) -> Option<String> {
+        let name = self
+            .app_id
+            .as_deref()
+            .or_else(|| self.window_properties.as_ref()?.class.as_deref())?
+            .to_lowercase();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// Reads the focused window from the sway (or any i3-ipc compatible) socket.
+pub struct SwayDetector {
+    socket: PathBuf,
+}
+
+impl SwayDetector {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    fn get_tree(&self) -> std::io::Result<Vec<u8>> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+        stream.set_write_timeout(Some(IPC_TIMEOUT))?;
+
+        let mut request = Vec::with_capacity(14);
+        request.extend_from_slice(IPC_MAGIC);
+        request.extend_from_slice(&0u32.to_ne_bytes());
+        request.extend_from_slice(&IPC_GET_TREE.to_ne_bytes());
+        stream.write_all(&request)?;
+
+        let mut header = [0u8; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..6] != IPC_MAGIC {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid i3-ipc reply magic",
+            ));
+        }
+        let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]) as usize;
+
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload)?;
+        Ok(payload)
+    }
+}
+
+impl ClientDetector for SwayDetector {
+    fn detect(&self) -> Option<String> {
+        #[cfg(feature = "debug")]
+        debug!(
+            "Attempting to detect client from sway IPC at {}...",
+            self.socket.display()
+        );
+
+        let payload = self.get_tree().ok()?;
+        let tree: SwayNode = serde_json::from_slice(&payload).ok()?;
+
+        let Some(name) = tree.find_focused().and_then(SwayNode::client_name) else {
+            #[cfg(feature = "debug")]
+            debug!("Could not find a focused window with an app_id or class");
+            return None;
+        };
+
+        #[cfg(feature = "debug")]
+        info!("Detected client from sway focused window: '{}'", name);
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    fn serve_once(listener: UnixListener, reply: &'static str) -> thread::JoinHandle<u32> {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 14];
+            stream.read_exact(&mut header).unwrap();
+            assert_eq!(&header[..6], IPC_MAGIC);
+            let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+            let mut response = Vec::new();
+            response.extend_from_slice(IPC_MAGIC);
+            response.extend_from_slice(&(reply.len() as u32).to_ne_bytes());
+            response.extend_from_slice(&kind.to_ne_bytes());
+            response.extend_from_slice(reply.as_bytes());
+            stream.write_all(&response).unwrap();
+            kind
+        })
+    }
+
+    fn detect_with_reply(reply: &'static str) -> Option<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("sway-ipc.sock");
+        let server = serve_once(UnixListener::bind(&socket).unwrap(), reply);
+
+        let client = SwayDetector::new(&socket).detect();
+        assert_eq!(server.join().unwrap(), IPC_GET_TREE);
+        client
+    }
+
+    #[test]
+    fn test_focused_app_id() {
+        let tree = r#"{
+            "focused": false,
+            "nodes": [{
+                "focused": false,
+                "nodes": [
+                    {"focused": false, "app_id": "foot", "nodes": []},
+                    {"focused": true, "app_id": "Slack", "nodes": []}
+                ]
+            }]
+        }"#;
+        assert_eq!(detect_with_reply(tree), Some("slack".to_string()));
+    }
+
+    #[test]
+    fn test_focused_xwayland_class() {
+        let tree = r#"{
+            "nodes": [{
+                "nodes": [],
+                "floating_nodes": [{
+                    "focused": true,
+                    "app_id": null,
+                    "window_properties": {"class": "Thunderbird"}
+                }]
+            }]
+        }"#;
+        assert_eq!(detect_with_reply(tree), Some("thunderbird".to_string()));
+    }
+
+    #[test]
+    fn test_no_focused_window() {
+        let tree = r#"{"focused": true, "nodes": []}"#;
+        assert_eq!(detect_with_reply(tree), None);
+    }
+
+    #[test]
+    fn test_missing_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            SwayDetector::new(dir.path().join("nope.sock")).detect(),
+            None
+        );
+    }
+}
@@ -3,16 +3,31 @@
 //! This module handles reading and parsing the TOML configuration file,
 //! including default browser settings and routing rules.
 
-use anyhow::{Context, Result};
+use crate::matcher::ClientMatcher;
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub default: DefaultSection,
+    #[serde(default)]
+    pub focus_history: FocusHistorySection,
+    #[serde(default)]
+    pub picker: PickerSection,
+    /// Rewrites applied to every link after the section's own.
+    #[serde(default)]
+    pub rewrite: Vec<RewriteRule>,
+    #[serde(default)]
+    pub privacy: PrivacySection,
+    #[serde(default)]
+    pub unwrap: UnwrapSection,
+    #[serde(default)]
+    pub matching: MatchingSection,
     #[serde(flatten)]
     pub sections: HashMap<String, RuleSection>,
 }
@@ -21,27 +36,332 @@ pub struct Config {
 pub struct DefaultSection {
     #[serde(default = "default_browser")]
     pub browser: String,
+    #[serde(default)]
+    pub action: Action,
+    #[serde(default)]
+    pub command: Vec<String>,
+}
+
+/// What to do with a link once a rule, or the default, has matched it.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Open the link in the section's browser.
+    #[default]
+    Launch,
+    /// Ask which browser to use through a dmenu-compatible picker.
+    Picker,
+    /// Copy the link to the clipboard with `wl-copy`.
+    Copy,
+    /// Show the link in a desktop notification without opening it.
+    Notify,
+    /// Drop the link.
+    Ignore,
+    /// Run the section's `command`, with placeholders filled from the link.
+    Command,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::Launch => "launch",
+            Action::Picker => "picker",
+            Action::Copy => "copy",
+            Action::Notify => "notify",
+            Action::Ignore => "ignore",
+            Action::Command => "command",
+        })
+    }
+}
+
+impl Action {
+    /// Whether the action opens the link in the section's browser.
+    pub fn uses_browser(self) -> bool {
+        matches!(self, Action::Launch | Action::Picker)
+    }
+}
+
+/// Focus tracking done by the daemon, used to find the window that owns the caller.
+#[derive(Debug, Deserialize)]
+pub struct FocusHistorySection {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_history_size")]
+    pub size: usize,
+}
+
+impl Default for FocusHistorySection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: default_history_size(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// The dmenu-style menu used by `action = "picker"`.
+#[derive(Debug, Deserialize, Default)]
+pub struct PickerSection {
+    /// Shell command that reads choices on stdin and prints the selected one.
+    /// When unset, the first of fuzzel, wofi, rofi, tofi, bemenu or dmenu found is used.
+    pub command: Option<String>,
+    /// Offer to save the choice as a host rule.
+    #[serde(default)]
+    pub remember: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct RuleSection {
+    /// Not needed when `action` doesn't open a browser.
+    #[serde(default)]
     pub browser: String,
     #[serde(default)]
-    pub clients: Vec<String>,
+    pub clients: Vec<ClientMatcher>,
     #[serde(default)]
     pub url: Vec<String>,
+    #[serde(default)]
+    pub action: Action,
+    /// Program and arguments for `action = "command"`, e.g. `["mpv", "{url}"]`.
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub rewrite: Vec<RewriteRule>,
+    /// Link schemes this section handles, e.g. `["mailto"]`; web links when empty.
+    #[serde(default)]
+    pub schemes: Vec<String>,
+    /// Registrable domains, e.g. `alice.github.io`, matched with the Public Suffix List.
+    #[serde(default)]
+    pub domain: Vec<String>,
+    /// IP networks such as `10.0.0.0/8` or single addresses, for links to IP hosts.
+    #[serde(default)]
+    pub cidr: Vec<String>,
+    /// Hosts, with their subdomains, this section never handles.
+    #[serde(default)]
+    pub exclude_url: Vec<String>,
+    /// Clients this section never handles.
+    #[serde(default)]
+    pub exclude_clients: Vec<ClientMatcher>,
+}
+
+/// Schemes a section handles when it doesn't list `schemes`.
+pub const WEB_SCHEMES: &[&str] = &["http", "https", "file"];
+
+impl RuleSection {
+    /// Whether links with `scheme` can match this section.
+    pub fn accepts_scheme(&self, scheme: &str) -> bool {
+        if self.schemes.is_empty() {
+            return WEB_SCHEMES.contains(&scheme);
+        }
+        self.schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme))
+    }
+}
+
+/// How hosts are compared with `url` patterns.
+#[derive(Debug, Deserialize, Default)]
+pub struct MatchingSection {
+    /// Let `example.com` match a `www.example.com` pattern too.
+    #[serde(default)]
+    pub ignore_www: bool,
+}
+
+/// Tracking parameter removal, applied before a link is routed.
+#[derive(Debug, Deserialize, Default)]
+pub struct PrivacySection {
+    #[serde(default)]
+    pub strip_params: bool,
+    /// Parameters to strip on top of the built-in list; `prefix*` matches a prefix.
+    #[serde(default)]
+    pub params: Vec<String>,
+    /// Overrides for a host and its subdomains.
+    #[serde(default)]
+    pub hosts: HashMap<String, HostPrivacy>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct HostPrivacy {
+    /// Parameters this host needs, even if they are on the strip list.
+    #[serde(default)]
+    pub keep: Vec<String>,
+    /// Extra parameters to strip for this host.
+    #[serde(default)]
+    pub strip: Vec<String>,
+}
+
+/// Extraction of the real target from redirect wrappers, done before routing.
+#[derive(Debug, Deserialize)]
+pub struct UnwrapSection {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Wrappers to unwrap on top of the built-in ones.
+    #[serde(default)]
+    pub rules: Vec<UnwrapRule>,
+}
+
+impl Default for UnwrapSection {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// A redirect wrapper: links to `host` (and subdomains), optionally only at
+/// `path`, carry the target URL in the `param` query parameter.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct UnwrapRule {
+    pub host: String,
+    pub path: Option<String>,
+    pub param: String,
+}
+
+/// Rewrites links matching `from`, e.g. `twitter.com` to `xcancel.com`.
+///
+/// `from` is a host, optionally with a scheme and a path whose `{name}`
+/// segments capture; `to` may use the captures and the command placeholders.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct RewriteRule {
+    pub from: String,
+    pub to: String,
+}
+
+/// Top-level tables that configure hyprchoosy rather than define a rule.
+pub(crate) const RESERVED_SECTIONS: &[&str] = &[
+    "default",
+    "focus_history",
+    "picker",
+    "rewrite",
+    "privacy",
+    "unwrap",
+    "matching",
+];
+
+/// Keys only a rule section has. A reserved table holding one is a rule
+/// section that was named before the table became reserved.
+const RULE_ONLY_KEYS: &[&str] = &[
+    "browser",
+    "clients",
+    "url",
+    "domain",
+    "cidr",
+    "schemes",
+    "exclude_url",
+    "exclude_clients",
+];
+
+/// Returns the rule key in the reserved table `name`, if `keys` has one.
+pub(crate) fn misplaced_rule_key<'a>(
+    name: &str,
+    mut keys: impl Iterator<Item = &'a str>,
+) -> Option<&'a str> {
+    if name == "default" || !RESERVED_SECTIONS.contains(&name) {
+        return None;
+    }
+    keys.find(|key| RULE_ONLY_KEYS.contains(key))
+}
+
+/// Explains why the reserved table `name` can't hold the rule key `key`.
+pub(crate) fn misplaced_rule_message(name: &str, key: &str) -> String {
+    format!(
+        "[{}] is reserved for hyprchoosy settings, but has the rule key '{}'; rename the section, e.g. to [{}-links]",
+        name, key, name
+    )
+}
+
+/// Parses config TOML, refusing rule sections that clash with a reserved table.
+///
+/// Without this check serde would fill the settings table and silently drop the rules.
+pub fn parse_config(data: &str) -> Result<Config> {
+    let table: toml::Table = toml::from_str(data).with_context(|| "Invalid TOML in config")?;
+    for (name, item) in &table {
+        let keys = item.as_table().into_iter().flat_map(|t| t.keys());
+        if let Some(key) = misplaced_rule_key(name, keys.map(String::as_str)) {
+            bail!(misplaced_rule_message(name, key));
+        }
+    }
+    toml::from_str(data).with_context(|| "Invalid TOML in config")
 }
 
 fn default_browser() -> String {
     "firefox".to_string()
 }
 
-fn xdg_config_home() -> PathBuf {
-    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+fn default_true() -> bool {
+    true
+}
+
+fn default_history_size() -> usize {
+    32
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    if let Ok(dir) = env::var(var) {
         return PathBuf::from(dir);
     }
     let home = env::var("HOME").unwrap_or_else(|_| ".".into());
-    PathBuf::from(home).join(".config")
+    PathBuf::from(home).join(fallback)
+}
+
+pub(crate) fn xdg_config_home() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub(crate) fn xdg_data_home() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+pub(crate) fn xdg_state_home() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Per-session directory for the daemon socket and state files.
+///
+/// Without `XDG_RUNTIME_DIR` this is a predictable path under `/tmp`, so it is
+/// created private and refused if someone else could have planted it.
+pub fn runtime_dir() -> Result<PathBuf> {
+    if let Ok(dir) = env::var("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("hyprchoosy-{}", uid));
+    private_dir(&dir, uid)?;
+    Ok(dir)
+}
+
+/// Creates `dir` with mode 0700 if needed and checks that only `uid` can use it.
+fn private_dir(dir: &Path, uid: u32) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to create {}", dir.display()));
+        }
+    }
+
+    // `symlink_metadata` doesn't follow a planted symlink to a directory elsewhere.
+    let meta = std::fs::symlink_metadata(dir)
+        .with_context(|| format!("Failed to inspect {}", dir.display()))?;
+    if !meta.file_type().is_dir() {
+        bail!("Refusing to use {}: not a directory", dir.display());
+    }
+    if meta.uid() != uid {
+        bail!(
+            "Refusing to use {}: owned by uid {}, not {}",
+            dir.display(),
+            meta.uid(),
+            uid
+        );
+    }
+    if meta.mode() & 0o077 != 0 {
+        bail!(
+            "Refusing to use {}: mode {:o} lets other users in, expected 700",
+            dir.display(),
+            meta.mode() & 0o777
+        );
+    }
+    Ok(())
 }
 
 pub fn config_path() -> PathBuf {
@@ -59,14 +379,72 @@ pub fn load_config() -> Result<Config> {
             path.display()
         )
     })?;
-    let cfg: Config = toml::from_str(&data).with_context(|| "Invalid TOML in config")?;
-    Ok(cfg)
+    parse_config(&data)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rule_section_named_like_reserved_table() {
+        let err =
+            parse_config("[picker]\nbrowser = \"chromium\"\nurl = [\"a.com\"]\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[picker] is reserved for hyprchoosy settings, but has the rule key 'browser'; rename the section, e.g. to [picker-links]"
+        );
+        assert!(parse_config("[matching]\nurl = [\"a.com\"]\n").is_err());
+
+        let cfg =
+            parse_config("[picker]\ncommand = \"fuzzel --dmenu\"\n[default]\nbrowser = \"x\"\n")
+                .unwrap();
+        assert_eq!(cfg.picker.command.as_deref(), Some("fuzzel --dmenu"));
+    }
+
+    #[test]
+    fn test_invalid_client_regex() {
+        let err = parse_config("[editor]\nbrowser = \"chromium\"\nexclude_clients = [\"/(/\"]\n")
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("'/(/' is not a valid regex"),
+            "{:#}",
+            err
+        );
+
+        let cfg =
+            parse_config("[editor]\nbrowser = \"chromium\"\nclients = [\"/^(zed|cursor)$/\"]\n")
+                .unwrap();
+        assert!(cfg.sections["editor"].clients[0].matches("Zed"));
+    }
+
+    #[test]
+    fn test_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let uid = unsafe { libc::getuid() };
+        let dir = tmp.path().join("hyprchoosy-1000");
+
+        private_dir(&dir, uid).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        private_dir(&dir, uid).unwrap();
+
+        assert!(private_dir(&dir, uid + 1).is_err());
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(private_dir(&dir, uid).is_err());
+
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(tmp.path(), &link).unwrap();
+        assert!(private_dir(&link, uid).is_err());
+
+        let file = tmp.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(private_dir(&file, uid).is_err());
+    }
+
     #[test]
     fn test_config_deserialization() {
         let toml_str = r#"
@@ -126,6 +504,169 @@ browser = "chrome"
         assert_eq!(section.url.len(), 0);
     }
 
+    #[test]
+    fn test_config_focus_history() {
+        let toml_str = r#"
+[focus_history]
+enabled = true
+
+[work]
+browser = "chrome"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.focus_history.enabled);
+        assert_eq!(config.focus_history.size, 32);
+        assert_eq!(config.sections.len(), 1);
+
+        let config: Config = toml::from_str("[work]\nbrowser = \"chrome\"").unwrap();
+        assert!(!config.focus_history.enabled);
+    }
+
+    #[test]
+    fn test_config_picker() {
+        let toml_str = r#"
+[default]
+browser = "firefox"
+action = "picker"
+
+[picker]
+command = "fuzzel --dmenu"
+remember = true
+
+[docs]
+browser = "chromium"
+url = ["docs.google.com"]
+action = "picker"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.default.action, Action::Picker);
+        assert_eq!(config.picker.command.as_deref(), Some("fuzzel --dmenu"));
+        assert!(config.picker.remember);
+        assert_eq!(config.sections.len(), 1);
+        assert_eq!(config.sections["docs"].action, Action::Picker);
+
+        let config: Config = toml::from_str("[work]\nbrowser = \"chrome\"").unwrap();
+        assert_eq!(config.default.action, Action::Launch);
+        assert_eq!(config.sections["work"].action, Action::Launch);
+        assert!(config.picker.command.is_none());
+    }
+
+    #[test]
+    fn test_config_actions_without_browser() {
+        let toml_str = r#"
+[tracking]
+clients = ["mailchimp"]
+action = "notify"
+
+[zoom]
+url = ["zoom.us"]
+action = "copy"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.sections["tracking"].action, Action::Notify);
+        assert_eq!(config.sections["zoom"].action, Action::Copy);
+        assert_eq!(config.sections["zoom"].browser, "");
+        assert!(!Action::Ignore.uses_browser());
+
+        let config: Config = toml::from_str(
+            r#"
+[video]
+url = ["youtube.com"]
+action = "command"
+command = ["mpv", "{url}"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.sections["video"].action, Action::Command);
+        assert_eq!(config.sections["video"].command, vec!["mpv", "{url}"]);
+        assert!(Action::Picker.uses_browser());
+    }
+
+    #[test]
+    fn test_config_rewrite() {
+        let toml_str = r#"
+[[rewrite]]
+from = "twitter.com"
+to = "xcancel.com"
+
+[social]
+browser = "brave"
+url = ["reddit.com"]
+rewrite = [{ from = "www.reddit.com", to = "old.reddit.com" }]
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.rewrite.len(), 1);
+        assert_eq!(config.rewrite[0].to, "xcancel.com");
+        assert_eq!(config.sections.len(), 1);
+        assert_eq!(config.sections["social"].rewrite[0].from, "www.reddit.com");
+    }
+
+    #[test]
+    fn test_config_privacy() {
+        let toml_str = r#"
+[privacy]
+strip_params = true
+params = ["ref_src"]
+
+[privacy.hosts."youtube.com"]
+keep = ["si"]
+
+[work]
+browser = "chrome"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.privacy.strip_params);
+        assert_eq!(config.privacy.params, vec!["ref_src"]);
+        assert_eq!(config.privacy.hosts["youtube.com"].keep, vec!["si"]);
+        assert_eq!(config.sections.len(), 1);
+    }
+
+    #[test]
+    fn test_config_unwrap() {
+        let config: Config = toml::from_str("[work]\nbrowser = \"chrome\"").unwrap();
+        assert!(config.unwrap.enabled);
+        assert!(config.unwrap.rules.is_empty());
+
+        let toml_str = r#"
+[unwrap]
+enabled = false
+rules = [{ host = "links.example.com", param = "target" }]
+"#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert!(!config.unwrap.enabled);
+        assert_eq!(config.unwrap.rules[0].host, "links.example.com");
+        assert_eq!(config.unwrap.rules[0].path, None);
+        assert!(config.sections.is_empty());
+    }
+
+    #[test]
+    fn test_config_schemes() {
+        let toml_str = r#"
+[mail]
+browser = "thunderbird"
+schemes = ["mailto"]
+
+[web]
+browser = "firefox"
+"#;
+
+        let config: Config = toml::from_str(toml_str).unwrap();
+        let mail = &config.sections["mail"];
+        assert!(mail.accepts_scheme("mailto"));
+        assert!(mail.accepts_scheme("MAILTO"));
+        assert!(!mail.accepts_scheme("https"));
+
+        let web = &config.sections["web"];
+        assert!(web.accepts_scheme("https"));
+        assert!(web.accepts_scheme("file"));
+        assert!(!web.accepts_scheme("mailto"));
+    }
+
     #[test]
     fn test_xdg_config_home_with_env() {
         std::env::set_var("XDG_CONFIG_HOME", "/custom/config");
@@ -0,0 +1,567 @@
+//! Long-running daemon and the shim that forwards URLs to it.
+//!
+//! The daemon keeps the parsed configuration and the focused window in memory,
+//! so `hyprchoosy <URL>...` only has to hand the URLs over a Unix socket instead of
+//! parsing TOML, scanning the process table and shelling out to `hyprctl`.
+
+use crate::client_detection::{
+    active_window, ancestor_pids, focus_history_path, owning_client, window_detector,
+    ClientDetector, EnvDetector, FocusEntry, FocusHistory, ProcessTreeDetector,
+};
+use crate::config::{config_path, load_config, runtime_dir, Config};
+use crate::dispatch::{open_all, report, Outcome};
+use crate::launcher::{activation_token, ActivationToken};
+use crate::router::Overrides;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "debug")]
+use log::{debug, info, warn};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// URLs handed from the shim to the daemon.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub urls: Vec<String>,
+    /// Client to route for, skipping detection.
+    #[serde(default)]
+    pub client: Option<String>,
+    /// Section or browser forced on the command line.
+    #[serde(default)]
+    pub overrides: Overrides,
+    /// Client found in the shim's environment, which the daemon can't see.
+    pub client_hint: Option<String>,
+    /// PID of the shim, so the daemon can walk its process tree.
+    pub pid: u32,
+    /// Activation tokens the shim was started with, handed on to the browser.
+    pub activation_token: Option<ActivationToken>,
+}
+
+impl Request {
+    /// Builds a request for `urls` from the current process.
+    pub fn new(urls: &[String]) -> Self {
+        Self {
+            urls: urls.to_vec(),
+            client: None,
+            overrides: Overrides::default(),
+            client_hint: EnvDetector.detect(),
+            pid: std::process::id(),
+            activation_token: activation_token(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+    /// Why the request as a whole failed.
+    error: Option<String>,
+    #[serde(default)]
+    outcome: Outcome,
+    /// Client the links were routed for, so the shim routes the
+    /// [`Outcome::interactive`] links the same way.
+    #[serde(default)]
+    client: Option<String>,
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(runtime_dir()?.join("hyprchoosy.sock"))
+}
+
+/// Hands `request` to a running daemon.
+///
+/// Returns `None` when no daemon is listening, or the runtime directory can't
+/// be trusted, so the caller can route in-process.
+pub fn forward(request: &Request) -> Option<Result<()>> {
+    let socket = match socket_path() {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("hyprchoosy: not using the daemon: {:#}", e);
+            return None;
+        }
+    };
+    forward_to(&socket, request)
+}
+
+/// Whether a daemon is answering on its socket.
+pub fn is_running() -> bool {
+    socket_path().is_ok_and(|socket| UnixStream::connect(socket).is_ok())
+}
+
+fn forward_to(socket: &Path, request: &Request) -> Option<Result<()>> {
+    let stream = UnixStream::connect(socket).ok()?;
+
+    #[cfg(feature = "debug")]
+    info!(
+        "Forwarding {:?} to daemon at {}",
+        request.urls,
+        socket.display()
+    );
+
+    Some(exchange(stream, request).and_then(|response| finish(request, response)))
+}
+
+fn exchange(stream: UnixStream, request: &Request) -> Result<Response> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    write_line(&stream, request)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut reply)
+        .context("No reply from hyprchoosy daemon")?;
+    let response: Response =
+        serde_json::from_str(&reply).context("Invalid reply from hyprchoosy daemon")?;
+
+    match response.error {
+        Some(e) => bail!(e),
+        None => Ok(response),
+    }
+}
+
+/// Opens the links the daemon left to the shim, then reports failures across all links.
+///
+/// The daemon can't wait on a picker without stalling every other request, and
+/// the shim's read timeout would give up on it, so the menu is shown here.
+fn finish(request: &Request, response: Response) -> Result<()> {
+    let Response {
+        outcome, client, ..
+    } = response;
+    let mut failures = outcome.failures;
+    if !outcome.interactive.is_empty() {
+        #[cfg(feature = "debug")]
+        info!("Daemon left {:?} for the picker", outcome.interactive);
+
+        let cfg = load_config()?;
+        let picked = open_all(
+            &cfg,
+            &outcome.interactive,
+            client.as_deref(),
+            &request.overrides,
+            request.activation_token.as_ref(),
+            false,
+        );
+        failures.extend(picked.failures);
+    }
+    report(request.urls.len(), &failures)
+}
+
+fn write_line(mut stream: &UnixStream, value: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// Reads one request from `stream`, handles it and writes back the outcome.
+fn serve(stream: UnixStream, handle: impl FnOnce(&Request) -> Result<Response>) -> Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+
+    let mut line = String::new();
+    if BufReader::new(&stream).read_line(&mut line)? == 0 {
+        // A liveness probe from `bind` connects and hangs up without a request.
+        return Ok(());
+    }
+    let request: Request = serde_json::from_str(&line).context("Invalid request")?;
+
+    let response = handle(&request).unwrap_or_else(|e| Response {
+        error: Some(format!("{:#}", e)),
+        ..Response::default()
+    });
+    write_line(&stream, &response)
+}
+
+/// What the daemon knows about window focus.
+#[derive(Default)]
+struct Focus {
+    /// Class of the focused window, `None` when unknown.
+    current: Option<String>,
+    history: FocusHistory,
+}
+
+struct Daemon {
+    config: Config,
+    config_mtime: Option<SystemTime>,
+    focus: Arc<Mutex<Focus>>,
+    /// Focus history capacity, `None` when history tracking is off.
+    history_size: Option<usize>,
+}
+
+impl Daemon {
+    fn new() -> Result<Self> {
+        let config = load_config()?;
+        let history = &config.focus_history;
+        let history_size = history.enabled.then_some(history.size);
+
+        Ok(Self {
+            config,
+            config_mtime: config_mtime(),
+            focus: Arc::default(),
+            history_size,
+        })
+    }
+
+    fn reload_if_changed(&mut self) {
+        let mtime = config_mtime();
+        if mtime == self.config_mtime {
+            return;
+        }
+
+        match load_config() {
+            Ok(config) => {
+                #[cfg(feature = "debug")]
+                info!("Configuration changed, reloaded");
+                self.config = config;
+                self.config_mtime = mtime;
+            }
+            Err(e) => eprintln!("hyprchoosy daemon: keeping previous config: {:#}", e),
+        }
+    }
+
+    fn detect_client(&self, request: &Request) -> Option<String> {
+        let ancestors = ancestor_pids(request.pid);
+
+        if self.history_size.is_some() {
+            if let Some(owner) = self.focus.lock().unwrap().history.owner_of(&ancestors) {
+                #[cfg(feature = "debug")]
+                info!("Detected client from focus history: '{}'", owner.class);
+                return Some(owner.class.clone());
+            }
+        }
+
+        if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            if let Some(class) = owning_client(&ancestors) {
+                #[cfg(feature = "debug")]
+                info!("Detected client from window owning the caller: '{}'", class);
+                return Some(class);
+            }
+        }
+
+        if let Some(class) = self.focus.lock().unwrap().current.clone() {
+            #[cfg(feature = "debug")]
+            info!("Detected client from Hyprland event stream: '{}'", class);
+            return Some(class);
+        }
+
+        window_detector(request.pid)
+            .and_then(|detector| detector.detect())
+            .or_else(|| request.client_hint.clone())
+            .or_else(|| ProcessTreeDetector::from_pid(request.pid).detect())
+    }
+
+    fn handle(&mut self, request: &Request) -> Result<Response> {
+        #[cfg(feature = "debug")]
+        info!("Daemon received URLs: {:?}", request.urls);
+
+        self.reload_if_changed();
+        let client = request
+            .client
+            .clone()
+            .or_else(|| self.detect_client(request));
+        let outcome = open_all(
+            &self.config,
+            &request.urls,
+            client.as_deref(),
+            &request.overrides,
+            request.activation_token.as_ref(),
+            true,
+        );
+        Ok(Response {
+            error: None,
+            outcome,
+            client,
+        })
+    }
+}
+
+fn config_mtime() -> Option<SystemTime> {
+    fs::metadata(config_path()).ok()?.modified().ok()
+}
+
+/// Runs the daemon until the process is killed.
+pub fn run() -> Result<()> {
+    let socket = socket_path()?;
+    let listener = bind(&socket)?;
+    let mut daemon = Daemon::new()?;
+
+    // Start from a clean slate; PIDs from a previous session mean nothing now.
+    let focus_path = focus_history_path()?;
+    let _ = fs::remove_file(&focus_path);
+    remove_on_exit(vec![socket.clone(), focus_path]);
+
+    if let Some(events) = hyprland_event_socket() {
+        let focus = Arc::clone(&daemon.focus);
+        let history_size = daemon.history_size;
+        thread::spawn(move || watch_focus(&events, &focus, history_size));
+    }
+
+    #[cfg(feature = "debug")]
+    info!("Daemon listening on {}", socket.display());
+
+    for stream in listener.incoming() {
+        let result = stream
+            .context("Failed to accept connection")
+            .and_then(|stream| serve(stream, |request| daemon.handle(request)));
+        if let Err(e) = result {
+            eprintln!("hyprchoosy daemon: {:#}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes `paths` when the daemon is stopped with SIGINT, SIGTERM or SIGHUP.
+///
+/// Must be called before any other thread is spawned: the signals are blocked
+/// here and every later thread inherits that, so only the waiter sees them.
+fn remove_on_exit(paths: Vec<PathBuf>) {
+    let mut signals: libc::sigset_t = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::sigemptyset(&mut signals);
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            libc::sigaddset(&mut signals, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut());
+    }
+
+    thread::spawn(move || {
+        let mut signal = 0;
+        unsafe { libc::sigwait(&signals, &mut signal) };
+
+        #[cfg(feature = "debug")]
+        info!("Received signal {}, removing {:?}", signal, paths);
+
+        for path in &paths {
+            let _ = fs::remove_file(path);
+        }
+        std::process::exit(128 + signal);
+    });
+}
+
+fn bind(socket: &Path) -> Result<UnixListener> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            bail!(
+                "hyprchoosy daemon is already running at {}",
+                socket.display()
+            );
+        }
+        fs::remove_file(socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+
+    UnixListener::bind(socket).with_context(|| format!("Failed to listen on {}", socket.display()))
+}
+
+fn hyprland_event_socket() -> Option<PathBuf> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+    let runtime = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
+
+    let path = Path::new(&runtime)
+        .join("hypr")
+        .join(&signature)
+        .join(".socket2.sock");
+    if path.exists() {
+        return Some(path);
+    }
+
+    // Hyprland before 0.40 kept its sockets under /tmp.
+    Some(Path::new("/tmp/hypr").join(signature).join(".socket2.sock"))
+}
+
+/// Follows Hyprland's event socket and keeps `focus` up to date.
+fn watch_focus(socket: &Path, focus: &Mutex<Focus>, history_size: Option<usize>) {
+    loop {
+        match UnixStream::connect(socket) {
+            Ok(stream) => {
+                #[cfg(feature = "debug")]
+                info!("Connected to Hyprland event socket {}", socket.display());
+
+                let class = active_window().and_then(|w| w.client_name());
+                record_focus(focus, class, history_size);
+
+                for line in BufReader::new(stream).lines() {
+                    let Ok(line) = line else { break };
+                    if let Some(class) = parse_active_window(&line) {
+                        #[cfg(feature = "debug")]
+                        debug!("Focus changed to {:?}", class);
+                        record_focus(focus, class, history_size);
+                    }
+                }
+            }
+            Err(_e) => {
+                #[cfg(feature = "debug")]
+                warn!("Cannot connect to Hyprland event socket: {}", _e);
+            }
+        }
+
+        // Until we reconnect, fall back to asking hyprctl on every request.
+        focus.lock().unwrap().current = None;
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+fn record_focus(focus: &Mutex<Focus>, class: Option<String>, history_size: Option<usize>) {
+    // Events only carry the class, so ask Hyprland for the window's PID.
+    let entry = history_size
+        .filter(|_| class.is_some())
+        .and_then(|_| focused_entry());
+
+    let mut focus = focus.lock().unwrap();
+    focus.current = class;
+
+    if let (Some(entry), Some(size)) = (entry, history_size) {
+        focus.history.record(entry, size);
+        if let Err(e) = focus_history_path().and_then(|path| focus.history.save(&path)) {
+            eprintln!("hyprchoosy daemon: {:#}", e);
+        }
+    }
+}
+
+fn focused_entry() -> Option<FocusEntry> {
+    let window = active_window()?;
+    Some(FocusEntry {
+        class: window.client_name()?,
+        pid: u32::try_from(window.pid).ok()?,
+    })
+}
+
+/// Parses an `activewindow>>class,title` event.
+///
+/// Yields `Some(None)` when focus moved to no window at all.
+fn parse_active_window(line: &str) -> Option<Option<String>> {
+    let data = line.strip_prefix("activewindow>>")?;
+    let class = data.split(',').next().unwrap_or_default().to_lowercase();
+    Some((!class.is_empty()).then_some(class))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str) -> Request {
+        Request {
+            urls: vec![url.to_string()],
+            client: None,
+            overrides: Overrides::default(),
+            client_hint: Some("slack".to_string()),
+            pid: 4242,
+            activation_token: Some(ActivationToken {
+                xdg_activation: Some("token-1".to_string()),
+                startup_id: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_parse_active_window() {
+        assert_eq!(
+            parse_active_window("activewindow>>Slack,general - Slack"),
+            Some(Some("slack".to_string()))
+        );
+        assert_eq!(parse_active_window("activewindow>>,"), Some(None));
+        assert_eq!(parse_active_window("workspace>>2"), None);
+        assert_eq!(parse_active_window("activewindowv2>>55d1f0a0"), None);
+    }
+
+    #[test]
+    fn test_forward_without_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("hyprchoosy.sock");
+        assert!(forward_to(&socket, &request("https://github.com")).is_none());
+    }
+
+    #[test]
+    fn test_forward_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("hyprchoosy.sock");
+        let listener = bind(&socket).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut received = None;
+            serve(stream, |request| {
+                received = Some((request.urls.clone(), request.pid));
+                Ok(Response::default())
+            })
+            .unwrap();
+            received
+        });
+
+        let result = forward_to(&socket, &request("https://github.com")).unwrap();
+        assert!(result.is_ok());
+        assert_eq!(
+            server.join().unwrap(),
+            Some((vec!["https://github.com".to_string()], 4242))
+        );
+    }
+
+    #[test]
+    fn test_forward_reports_daemon_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("hyprchoosy.sock");
+        let listener = bind(&socket).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream, |_| bail!("Invalid URL: not a url")).unwrap();
+        });
+
+        let err = forward_to(&socket, &request("not a url"))
+            .unwrap()
+            .unwrap_err();
+        server.join().unwrap();
+        assert_eq!(err.to_string(), "Invalid URL: not a url");
+    }
+
+    #[test]
+    fn test_forward_reports_link_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("hyprchoosy.sock");
+        let listener = bind(&socket).unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream, |request| {
+                let failed = (request.urls[1].clone(), "No browser".to_string());
+                Ok(Response {
+                    outcome: Outcome {
+                        failures: vec![failed],
+                        interactive: Vec::new(),
+                    },
+                    ..Response::default()
+                })
+            })
+            .unwrap();
+        });
+
+        let mut request = request("https://github.com");
+        request.urls.push("https://example.com".to_string());
+        let err = forward_to(&socket, &request).unwrap().unwrap_err();
+        server.join().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Failed to open 1 of 2 links:\n  https://example.com: No browser"
+        );
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("hyprchoosy.sock");
+        drop(UnixListener::bind(&socket).unwrap());
+        assert!(socket.exists());
+
+        let listener = bind(&socket).unwrap();
+        assert!(bind(&socket).is_err());
+        drop(listener);
+    }
+}
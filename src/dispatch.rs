@@ -0,0 +1,349 @@
+//! Carrying out the action a route asks for.
+//!
+//! This module is shared by the in-process path and the daemon so both treat
+//! every action the same way.
+
+use crate::config::{Action, Config};
+use crate::launcher::{
+    launch_browser_urls, launch_browser_with_token, launch_command, ActivationToken,
+};
+use crate::matcher::clean_url;
+use crate::picker;
+use crate::rewrite;
+use crate::router::{route_with, Overrides, Route};
+use crate::template::expand_all;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "debug")]
+use log::info;
+
+/// Opens `url` as `route` says, handing `token` on to whatever gets launched.
+///
+/// The route's rewrite rules are applied to `url` first.
+pub fn dispatch(
+    cfg: &Config,
+    url: &str,
+    route: &Route,
+    token: Option<&ActivationToken>,
+) -> Result<()> {
+    let routed = url;
+    let url = &rewrite::apply(cfg, route, url);
+
+    #[cfg(feature = "debug")]
+    info!("Dispatching action {:?} for '{}'", route.action, url);
+
+    match route.action {
+        Action::Launch if route.browser.trim().is_empty() => {
+            bail!("No browser configured for '{}'", url)
+        }
+        Action::Launch => launch_browser_with_token(route.browser, url, token),
+        Action::Picker => picker::run(cfg, url, routed, route.browser, token),
+        Action::Copy => spawn("wl-copy", &["--", url]),
+        Action::Notify => spawn(
+            "notify-send",
+            &[
+                "--app-name=hyprchoosy",
+                "Link not opened",
+                &escape_markup(url),
+            ],
+        ),
+        Action::Ignore => {
+            #[cfg(feature = "debug")]
+            info!("Ignoring '{}'", url);
+            Ok(())
+        }
+        Action::Command if route.command.is_empty() => {
+            bail!("No command configured for '{}'", url)
+        }
+        Action::Command => launch_command(&expand_all(route.command, url), token),
+    }
+}
+
+/// What became of the links handed to [`open_all`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outcome {
+    /// Links that failed, with the reason.
+    pub failures: Vec<(String, String)>,
+    /// Links left unopened because they need a picker on screen.
+    pub interactive: Vec<String>,
+}
+
+/// Cleans, routes and opens each of `urls` for `client`, honouring `overrides`.
+///
+/// Links launched in the same browser go to one process, so they open as tabs
+/// of one window. A link that fails doesn't stop the others; the error lists
+/// every link that failed.
+pub fn dispatch_all(
+    cfg: &Config,
+    urls: &[String],
+    client: Option<&str>,
+    overrides: &Overrides,
+    token: Option<&ActivationToken>,
+) -> Result<()> {
+    let outcome = open_all(cfg, urls, client, overrides, token, false);
+    report(urls.len(), &outcome.failures)
+}
+
+/// Does the work of [`dispatch_all`] and returns what happened to each link.
+///
+/// With `defer_interactive`, links routed to the picker are returned in
+/// [`Outcome::interactive`] instead of blocking on a menu. The daemon uses this
+/// so the shim that called it can show the menu.
+pub fn open_all(
+    cfg: &Config,
+    urls: &[String],
+    client: Option<&str>,
+    overrides: &Overrides,
+    token: Option<&ActivationToken>,
+    defer_interactive: bool,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    let mut batches: Vec<(&str, Vec<String>)> = Vec::new();
+
+    for original in urls {
+        if original.trim().is_empty() {
+            outcome
+                .failures
+                .push((original.clone(), "Empty URL".to_string()));
+            continue;
+        }
+        let url = clean_url(cfg, original);
+        let result = route_with(cfg, &url, client, overrides).and_then(|route| {
+            if defer_interactive && route.action == Action::Picker {
+                outcome.interactive.push(original.clone());
+                return Ok(());
+            }
+            if route.action != Action::Launch || route.browser.trim().is_empty() {
+                return dispatch(cfg, &url, &route, token);
+            }
+            let url = rewrite::apply(cfg, &route, &url);
+            match batches.iter_mut().find(|(b, _)| *b == route.browser) {
+                Some((_, batch)) => batch.push(url),
+                None => batches.push((route.browser, vec![url])),
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            outcome
+                .failures
+                .push((original.clone(), format!("{:#}", e)));
+        }
+    }
+
+    for (browser, batch) in &batches {
+        if let Err(e) = launch_browser_urls(browser, batch, token) {
+            let error = format!("{:#}", e);
+            let failed = batch.iter().map(|url| (url.clone(), error.clone()));
+            outcome.failures.extend(failed);
+        }
+    }
+    outcome
+}
+
+/// Turns the `failures` among `total` links into one error.
+///
+/// A single link reports its error as is; otherwise every failed link is listed.
+pub fn report(total: usize, failures: &[(String, String)]) -> Result<()> {
+    match failures {
+        [] => Ok(()),
+        [(_, error)] if total == 1 => bail!("{}", error),
+        _ => {
+            let lines: Vec<String> = failures
+                .iter()
+                .map(|(url, error)| match url.trim() {
+                    "" => format!("  (empty): {}", error),
+                    _ => format!("  {}: {}", url, error),
+                })
+                .collect();
+            bail!(
+                "Failed to open {} of {} links:\n{}",
+                failures.len(),
+                total,
+                lines.join("\n")
+            )
+        }
+    }
+}
+
+/// Starts a helper without waiting for it to finish.
+///
+/// The daemon answers one request at a time, so a slow `notify-send` must not
+/// hold up the reply.
+fn spawn(program: &str, args: &[&str]) -> Result<()> {
+    let argv: Vec<String> = std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect();
+    launch_command(&argv, None)
+}
+
+/// Notification bodies are markup; a query string's `&` would break it.
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+
+[tracking]
+clients = ["mailchimp"]
+action = "ignore"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ignore() {
+        let cfg = config();
+        let route = crate::router::route(&cfg, "https://t.example.com", Some("mailchimp")).unwrap();
+        assert_eq!(route.action, Action::Ignore);
+        assert!(dispatch(&cfg, "https://t.example.com", &route, None).is_ok());
+    }
+
+    #[test]
+    fn test_launch_without_browser_or_command() {
+        let cfg = config();
+        let route = Route {
+            section: None,
+            browser: "",
+            action: Action::Launch,
+            command: &[],
+            rewrite: &[],
+        };
+        assert!(dispatch(&cfg, "https://example.com", &route, None).is_err());
+
+        let route = Route {
+            action: Action::Command,
+            ..route
+        };
+        assert!(dispatch(&cfg, "https://example.com", &route, None).is_err());
+    }
+
+    #[test]
+    fn test_dispatch_all_batches_per_browser() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let browser = dir.path().join("browser");
+        std::fs::write(
+            &browser,
+            format!("#!/bin/sh\necho \"$*\" >> {}\n", log.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&browser, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cfg: Config = toml::from_str(&format!(
+            r#"
+[default]
+browser = "{}"
+
+[tracking]
+url = ["t.example.com"]
+action = "ignore"
+"#,
+            browser.display()
+        ))
+        .unwrap();
+
+        let urls = [
+            "https://a.example.com",
+            "not a url",
+            "",
+            "https://t.example.com",
+            "https://b.example.com",
+        ]
+        .map(String::from);
+        let err = dispatch_all(&cfg, &urls, None, &Overrides::default(), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to open 2 of 5 links:\n  not a url: Invalid URL: not a url: invalid international domain name\n  (empty): Empty URL"
+        );
+
+        let mut launched = String::new();
+        for _ in 0..100 {
+            launched = std::fs::read_to_string(&log).unwrap_or_default();
+            if launched.ends_with('\n') {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(launched, "https://a.example.com https://b.example.com\n");
+    }
+
+    #[test]
+    fn test_dispatch_all_single_failure() {
+        let cfg = config();
+        let err = dispatch_all(
+            &cfg,
+            &["not a url".to_string()],
+            None,
+            &Overrides::default(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid URL: not a url: invalid international domain name"
+        );
+    }
+
+    #[test]
+    fn test_open_all_defers_picker() {
+        let cfg: Config = toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+action = "picker"
+
+[tracking]
+clients = ["mailchimp"]
+action = "ignore"
+"#,
+        )
+        .unwrap();
+        let urls = ["https://a.example.com", "https://b.example.com"].map(String::from);
+
+        let outcome = open_all(&cfg, &urls, None, &Overrides::default(), None, true);
+        assert!(outcome.failures.is_empty());
+        assert_eq!(outcome.interactive, urls);
+
+        let outcome = open_all(
+            &cfg,
+            &urls,
+            Some("mailchimp"),
+            &Overrides::default(),
+            None,
+            true,
+        );
+        assert!(outcome.failures.is_empty());
+        assert!(outcome.interactive.is_empty());
+    }
+
+    #[test]
+    fn test_spawn_does_not_wait() {
+        let start = std::time::Instant::now();
+        assert!(spawn("sleep", &["5"]).is_ok());
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        assert!(spawn("hyprchoosy-no-such-helper", &[]).is_err());
+    }
+
+    #[test]
+    fn test_escape_markup() {
+        assert_eq!(
+            escape_markup("https://e.com/?a=1&b=<2>"),
+            "https://e.com/?a=1&amp;b=&lt;2&gt;"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Registrable-domain lookup for `domain` rules.
+//!
+//! This module finds the part of a host that was actually registered, such as
+//! `example.co.uk` for `www.example.co.uk` or `alice.github.io` for
+//! `docs.alice.github.io`, using a Public Suffix List embedded at build time.
+
+use crate::matcher::normalize_host;
+use publicsuffix::{List, Psl};
+use std::sync::OnceLock;
+
+/// Snapshot of <https://publicsuffix.org/list/public_suffix_list.dat>.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+fn list() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        PUBLIC_SUFFIX_LIST
+            .parse()
+            .expect("embedded public suffix list is valid")
+    })
+}
+
+/// Returns the registrable domain of `host`, or `None` for IP addresses,
+/// public suffixes such as `co.uk` or `github.io`, and empty hosts.
+pub fn registrable_domain(host: &str) -> Option<String> {
+    let host = normalize_host(host);
+    if host.is_empty() || host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok() {
+        return None;
+    }
+    // Unlisted TLDs such as `.internal` follow the list's implicit `*` rule.
+    let domain = list().domain(host.as_bytes())?;
+    Some(String::from_utf8_lossy(domain.as_bytes()).into_owned())
+}
+
+/// Whether `host` is itself a public suffix, so no `domain` rule can name it.
+pub fn is_public_suffix(host: &str) -> bool {
+    let host = normalize_host(host);
+    list()
+        .suffix(host.as_bytes())
+        .is_some_and(|suffix| suffix.is_known() && suffix.as_bytes() == host.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrable_domain() {
+        let cases = [
+            ("www.example.co.uk", Some("example.co.uk")),
+            ("example.co.uk", Some("example.co.uk")),
+            ("docs.alice.github.io", Some("alice.github.io")),
+            ("my-app.vercel.app", Some("my-app.vercel.app")),
+            ("API.Example.COM.", Some("example.com")),
+            ("www.münchen.de", Some("xn--mnchen-3ya.de")),
+            ("wiki.corp.internal", Some("corp.internal")),
+            ("co.uk", None),
+            ("localhost", None),
+            ("github.io", None),
+            ("192.168.1.1", None),
+            ("[::1]", None),
+            ("", None),
+        ];
+        for (host, expected) in cases {
+            assert_eq!(registrable_domain(host).as_deref(), expected, "{}", host);
+        }
+    }
+
+    #[test]
+    fn test_is_public_suffix() {
+        assert!(is_public_suffix("co.uk"));
+        assert!(is_public_suffix("github.io"));
+        assert!(is_public_suffix("herokuapp.com"));
+        assert!(!is_public_suffix("example.co.uk"));
+        assert!(!is_public_suffix("alice.github.io"));
+    }
+}
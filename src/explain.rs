@@ -0,0 +1,161 @@
+//! Routing explanation for `hyprchoosy explain`.
+//!
+//! This module shows which rule a URL matches, what would be done with it and
+//! how rewrites change it, without opening anything.
+
+use crate::client_detection::detect_client;
+use crate::config::{load_config, Config};
+use crate::matcher::clean_url;
+use crate::rewrite;
+use crate::router::{route_with, Overrides};
+use crate::template::expand_all;
+use anyhow::Result;
+use std::fmt::Write;
+
+/// Describes how `url` would be handled when opened from `client` with `overrides`.
+pub fn explain(
+    cfg: &Config,
+    url: &str,
+    client: Option<&str>,
+    overrides: &Overrides,
+) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "URL:     {}", url)?;
+
+    let cleaned = clean_url(cfg, url);
+    if cleaned != url {
+        writeln!(out, "Cleaned: {}", cleaned)?;
+    }
+    let url = cleaned.as_str();
+    let route = route_with(cfg, url, client, overrides)?;
+    let rewritten = rewrite::apply(cfg, &route, url);
+
+    writeln!(out, "Client:  {}", client.unwrap_or("(not detected)"))?;
+    writeln!(out, "Rule:    [{}]", route.section.unwrap_or("default"))?;
+    writeln!(out, "Action:  {}", route.action)?;
+    if route.action.uses_browser() {
+        writeln!(out, "Browser: {}", route.browser)?;
+    }
+    if !route.command.is_empty() {
+        writeln!(
+            out,
+            "Command: {}",
+            expand_all(route.command, &rewritten).join(" ")
+        )?;
+    }
+    if rewritten == url {
+        writeln!(out, "Rewrite: (none)")?;
+    } else {
+        writeln!(out, "Rewrite: {} -> {}", url, rewritten)?;
+    }
+    Ok(out)
+}
+
+/// Runs `hyprchoosy explain <URL>`, detecting the client unless `client` is given.
+pub fn run(url: &str, client: Option<String>, overrides: &Overrides) -> Result<()> {
+    let cfg = load_config()?;
+    let client = client.or_else(|| detect_client(&cfg));
+    print!("{}", explain(&cfg, url, client.as_deref(), overrides)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+[[rewrite]]
+from = "www.reddit.com"
+to = "old.reddit.com"
+
+[default]
+browser = "firefox"
+
+[social]
+browser = "brave"
+clients = ["slack"]
+rewrite = [{ from = "twitter.com", to = "xcancel.com" }]
+
+[zoom]
+url = ["zoom.us"]
+action = "command"
+command = ["zoom", "--url={url}"]
+rewrite = [{ from = "zoom.us/j/{id}", to = "zoommtg://zoom.us/join?confno={id}" }]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_explain_rewrite() {
+        let cfg = config();
+        assert_eq!(
+            explain(
+                &cfg,
+                "https://twitter.com/user",
+                Some("slack"),
+                &Overrides::default()
+            )
+            .unwrap(),
+            "URL:     https://twitter.com/user
+Client:  slack
+Rule:    [social]
+Action:  launch
+Browser: brave
+Rewrite: https://twitter.com/user -> https://xcancel.com/user
+"
+        );
+    }
+
+    #[test]
+    fn test_explain_default() {
+        let cfg = config();
+        assert_eq!(
+            explain(
+                &cfg,
+                "https://www.reddit.com/r/rust",
+                None,
+                &Overrides::default()
+            )
+            .unwrap(),
+            "URL:     https://www.reddit.com/r/rust
+Client:  (not detected)
+Rule:    [default]
+Action:  launch
+Browser: firefox
+Rewrite: https://www.reddit.com/r/rust -> https://old.reddit.com/r/rust
+"
+        );
+    }
+
+    #[test]
+    fn test_explain_cleaned() {
+        let mut cfg = config();
+        cfg.privacy.strip_params = true;
+        let out = explain(
+            &cfg,
+            "https://twitter.com/user?utm_source=x",
+            Some("slack"),
+            &Overrides::default(),
+        )
+        .unwrap();
+        assert!(out.starts_with(
+            "URL:     https://twitter.com/user?utm_source=x\nCleaned: https://twitter.com/user\n"
+        ));
+        assert!(out.ends_with("Rewrite: https://twitter.com/user -> https://xcancel.com/user\n"));
+    }
+
+    #[test]
+    fn test_explain_command() {
+        let cfg = config();
+        let out = explain(&cfg, "https://zoom.us/j/42", None, &Overrides::default()).unwrap();
+        assert!(out.contains(
+            "Rule:    [zoom]\nAction:  command\nCommand: zoom --url=zoommtg://zoom.us/join?confno=42\n"
+        ));
+
+        let out = explain(&cfg, "https://zoom.us/s/42", None, &Overrides::default()).unwrap();
+        assert!(out.ends_with("Rewrite: (none)\n"));
+    }
+}
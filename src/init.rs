@@ -0,0 +1,546 @@
+//! Starter configuration generator for `hyprchoosy init`.
+//!
+//! This module scans installed `.desktop` files for browsers and for chat and
+//! mail apps, then writes a commented config with rules for them.
+
+use crate::check::browser_exists;
+use crate::config::{config_path, xdg_data_home};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Desktop entry categories that mark an app whose links deserve their own rule.
+const CLIENT_GROUPS: &[(&str, &str, &[&str])] = &[
+    (
+        "chat",
+        "Links opened from chat apps",
+        &["Chat", "InstantMessaging"],
+    ),
+    ("mail", "Links opened from mail clients", &["Email"]),
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InitOptions {
+    pub non_interactive: bool,
+    pub force: bool,
+}
+
+/// The parts of a `.desktop` file `init` cares about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopEntry {
+    pub id: String,
+    pub name: String,
+    pub exec: Option<String>,
+    pub mime_types: Vec<String>,
+    pub categories: Vec<String>,
+    pub wm_class: Option<String>,
+}
+
+impl DesktopEntry {
+    pub fn parse(id: &str, contents: &str) -> Option<Self> {
+        let mut in_entry = false;
+        let mut fields = BTreeMap::new();
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_entry = line == "[Desktop Entry]";
+                continue;
+            }
+            if !in_entry || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                // Localized keys like `Name[de]` land under their own key and are ignored.
+                fields.insert(key.trim(), value.trim());
+            }
+        }
+
+        if fields.get("Type").is_some_and(|t| *t != "Application")
+            || fields.get("Hidden") == Some(&"true")
+        {
+            return None;
+        }
+
+        let list = |key: &str| -> Vec<String> {
+            fields
+                .get(key)
+                .map(|v| {
+                    v.split(';')
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            id: id.to_string(),
+            name: fields.get("Name").unwrap_or(&id).to_string(),
+            exec: fields.get("Exec").and_then(|e| program(e)),
+            mime_types: list("MimeType"),
+            categories: list("Categories"),
+            wm_class: fields.get("StartupWMClass").map(|s| s.to_string()),
+        })
+    }
+
+    fn is_browser(&self) -> bool {
+        let program = self
+            .exec
+            .as_deref()
+            .map(|e| e.rsplit('/').next().unwrap_or(e));
+        self.id != "hyprchoosy"
+            && program != Some("hyprchoosy")
+            && self.mime_types.iter().any(|m| m == "x-scheme-handler/http")
+    }
+
+    /// The name hyprchoosy will most likely detect for this app's windows.
+    fn client_name(&self) -> String {
+        let name = self.wm_class.as_deref().unwrap_or(&self.id);
+        name.rsplit('.').next().unwrap_or(name).to_lowercase()
+    }
+}
+
+/// Extracts the program from an `Exec` line, skipping `env VAR=value` prefixes.
+///
+/// Returns `None` for launchers like `flatpak run` that need extra arguments,
+/// since a rule's `browser` is a single command.
+fn program(exec: &str) -> Option<String> {
+    let tokens = exec_tokens(exec);
+    let program = tokens
+        .iter()
+        .find(|t| *t != "env" && !t.contains('='))?
+        .as_str();
+
+    let name = Path::new(program).file_name()?.to_str()?;
+    if matches!(name, "flatpak" | "snap" | "sh" | "bash") {
+        return None;
+    }
+
+    // Prefer the bare name when PATH finds it, it reads better in the config.
+    if program.contains('/') && browser_exists(name) {
+        return Some(name.to_string());
+    }
+    Some(program.to_string())
+}
+
+/// Splits an `Exec` line into arguments, honouring double quotes.
+fn exec_tokens(exec: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => current.extend(chars.next()),
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Directories `.desktop` files are installed to, highest priority first.
+pub fn applications_dirs() -> Vec<PathBuf> {
+    let data_home = xdg_data_home();
+    let data_dirs =
+        env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
+
+    std::iter::once(data_home)
+        .chain(env::split_paths(&data_dirs))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+/// Reads every desktop entry in `dirs`; earlier directories shadow later ones.
+pub fn scan_applications(dirs: &[PathBuf]) -> Vec<DesktopEntry> {
+    let mut entries = BTreeMap::new();
+
+    for dir in dirs {
+        let Ok(files) = fs::read_dir(dir) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("desktop") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if entries.contains_key(id) {
+                continue;
+            }
+            let entry = fs::read_to_string(&path)
+                .ok()
+                .and_then(|contents| DesktopEntry::parse(id, &contents));
+            entries.insert(id.to_string(), entry);
+        }
+    }
+
+    entries.into_values().flatten().collect()
+}
+
+/// What was found installed, grouped the way the config will be written.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    /// Browser commands, deduplicated.
+    pub browsers: Vec<String>,
+    /// Client names per entry of [`CLIENT_GROUPS`].
+    pub clients: Vec<Vec<String>>,
+}
+
+impl Inventory {
+    pub fn from_entries(entries: &[DesktopEntry]) -> Self {
+        let mut browsers: Vec<String> = entries
+            .iter()
+            .filter(|e| e.is_browser())
+            .filter_map(|e| e.exec.clone())
+            .collect();
+        browsers.sort();
+        browsers.dedup();
+
+        let clients = CLIENT_GROUPS
+            .iter()
+            .map(|(_, _, categories)| {
+                let mut names: Vec<String> = entries
+                    .iter()
+                    .filter(|e| !e.is_browser())
+                    .filter(|e| {
+                        e.categories
+                            .iter()
+                            .any(|c| categories.contains(&c.as_str()))
+                    })
+                    .map(DesktopEntry::client_name)
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            })
+            .collect();
+
+        Self { browsers, clients }
+    }
+}
+
+/// The browsers picked for `[default]` and each client group.
+#[derive(Debug)]
+pub struct Choices {
+    pub default: String,
+    /// Per entry of [`CLIENT_GROUPS`]; `None` writes the section commented out.
+    pub groups: Vec<Option<String>>,
+}
+
+impl Choices {
+    /// First browser as default, client groups left commented out.
+    pub fn suggested(inventory: &Inventory) -> Self {
+        Self {
+            default: inventory
+                .browsers
+                .first()
+                .cloned()
+                .unwrap_or_else(|| "firefox".into()),
+            groups: vec![None; CLIENT_GROUPS.len()],
+        }
+    }
+
+    /// Asks for each choice on `output`, reading answers from `input`.
+    ///
+    /// Empty answers and end of input keep the suggestion.
+    pub fn prompt(
+        inventory: &Inventory,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<Self> {
+        let mut choices = Self::suggested(inventory);
+        if inventory.browsers.is_empty() {
+            return Ok(choices);
+        }
+
+        writeln!(output, "Installed browsers:")?;
+        for (i, browser) in inventory.browsers.iter().enumerate() {
+            writeln!(output, "  {}) {}", i + 1, browser)?;
+        }
+
+        if let Some(i) = ask(input, output, "Default browser", Some(1), inventory)? {
+            choices.default = inventory.browsers[i].clone();
+        }
+
+        for (g, (_, description, _)) in CLIENT_GROUPS.iter().enumerate() {
+            let clients = &inventory.clients[g];
+            if clients.is_empty() {
+                continue;
+            }
+            let question = format!("{} ({}), 0 to skip", description, clients.join(", "));
+            choices.groups[g] = ask(input, output, &question, None, inventory)?
+                .map(|i| inventory.browsers[i].clone());
+        }
+
+        Ok(choices)
+    }
+}
+
+/// Reads a browser number; `None` means skip, or keep the default for `[default]`.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: Option<usize>,
+    inventory: &Inventory,
+) -> Result<Option<usize>> {
+    loop {
+        match default {
+            Some(d) => write!(output, "{} [{}]: ", question, d)?,
+            None => write!(output, "{} [0]: ", question)?,
+        }
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(default.map(|d| d - 1));
+        }
+
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(default.map(|d| d - 1));
+        }
+        match answer.parse::<usize>() {
+            Ok(0) if default.is_none() => return Ok(None),
+            Ok(n) if (1..=inventory.browsers.len()).contains(&n) => return Ok(Some(n - 1)),
+            _ => writeln!(output, "Please enter a number from the list.")?,
+        }
+    }
+}
+
+/// Renders the commented config for `choices`.
+pub fn render_config(inventory: &Inventory, choices: &Choices) -> String {
+    let mut out = String::new();
+    out.push_str("# hyprchoosy configuration, generated by `hyprchoosy init`.\n");
+    out.push_str("# Client rules win over url rules; links matching nothing use [default].\n");
+    out.push_str("# Check it with `hyprchoosy config check` after editing.\n\n");
+
+    out.push_str("[default]\n");
+    if !inventory.browsers.is_empty() {
+        out.push_str(&format!(
+            "# Installed browsers: {}\n",
+            inventory.browsers.join(", ")
+        ));
+    }
+    out.push_str(&format!("browser = {}\n", quote(&choices.default)));
+
+    for (g, (name, description, _)) in CLIENT_GROUPS.iter().enumerate() {
+        let clients = &inventory.clients[g];
+        if clients.is_empty() {
+            continue;
+        }
+
+        let clients = clients
+            .iter()
+            .map(|c| quote(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!("\n# {}\n", description));
+
+        match &choices.groups[g] {
+            Some(browser) => {
+                out.push_str(&format!("[{}]\n", name));
+                out.push_str(&format!("browser = {}\n", quote(browser)));
+                out.push_str(&format!("clients = [{}]\n", clients));
+            }
+            None => {
+                out.push_str(&format!("# [{}]\n", name));
+                out.push_str(&format!("# browser = {}\n", quote(&choices.default)));
+                out.push_str(&format!("# clients = [{}]\n", clients));
+            }
+        }
+    }
+
+    out.push_str("\n# Send domains (and their subdomains) to a browser:\n");
+    out.push_str("# [dev]\n");
+    out.push_str(&format!("# browser = {}\n", quote(&choices.default)));
+    out.push_str("# url = [\"github.com\", \"gitlab.com\"]\n");
+    out
+}
+
+fn quote(s: &str) -> String {
+    toml_edit::Value::from(s).to_string().trim().to_string()
+}
+
+/// Runs `hyprchoosy init`, returning the path of the written config.
+pub fn run(options: InitOptions) -> Result<PathBuf> {
+    let path = config_path();
+    if path.exists() && !options.force {
+        bail!(
+            "{} already exists; use --force to overwrite it",
+            path.display()
+        );
+    }
+
+    let inventory = Inventory::from_entries(&scan_applications(&applications_dirs()));
+    let choices = if options.non_interactive {
+        Choices::suggested(&inventory)
+    } else {
+        Choices::prompt(&inventory, &mut io::stdin().lock(), &mut io::stdout())?
+    };
+
+    write_config(&path, &render_config(&inventory, &choices))?;
+    Ok(path)
+}
+
+fn write_config(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn entry(id: &str, contents: &str) -> DesktopEntry {
+        DesktopEntry::parse(id, contents).unwrap()
+    }
+
+    fn entries() -> Vec<DesktopEntry> {
+        vec![
+            entry(
+                "firefox",
+                "[Desktop Entry]\nName=Firefox\nExec=firefox %u\nMimeType=text/html;x-scheme-handler/http;x-scheme-handler/https;\n",
+            ),
+            entry(
+                "chromium",
+                "[Desktop Entry]\nName=Chromium\nExec=env GTK_USE_PORTAL=1 chromium %U\nMimeType=x-scheme-handler/http;\n",
+            ),
+            entry(
+                "slack",
+                "[Desktop Entry]\nName=Slack\nExec=/usr/bin/slack -s %U\nCategories=Network;InstantMessaging;\nStartupWMClass=Slack\n",
+            ),
+            entry(
+                "org.mozilla.Thunderbird",
+                "[Desktop Entry]\nName=Thunderbird\nExec=thunderbird %u\nCategories=Network;Email;\n",
+            ),
+            entry(
+                "hyprchoosy",
+                "[Desktop Entry]\nName=Hyprchoosy\nExec=/usr/local/bin/hyprchoosy %u\nMimeType=x-scheme-handler/http;\n",
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        let entry = entry(
+            "slack",
+            "# comment\n[Desktop Entry]\nName=Slack\nName[de]=Slack DE\nExec=slack %U\nCategories=Network;InstantMessaging;\nStartupWMClass=Slack\n\n[Desktop Action new]\nExec=other\n",
+        );
+        assert_eq!(entry.name, "Slack");
+        assert_eq!(entry.exec.as_deref(), Some("slack"));
+        assert_eq!(entry.categories, vec!["Network", "InstantMessaging"]);
+        assert_eq!(entry.client_name(), "slack");
+    }
+
+    #[test]
+    fn test_parse_skips_hidden_and_links() {
+        assert!(DesktopEntry::parse("a", "[Desktop Entry]\nHidden=true\nExec=a\n").is_none());
+        assert!(DesktopEntry::parse("b", "[Desktop Entry]\nType=Link\nURL=x\n").is_none());
+    }
+
+    #[test]
+    fn test_program_skips_wrappers() {
+        assert_eq!(
+            program("env FOO=1 chromium %U").as_deref(),
+            Some("chromium")
+        );
+        assert_eq!(
+            program("\"/opt/My Browser/browser\" %u").as_deref(),
+            Some("/opt/My Browser/browser")
+        );
+        assert_eq!(program("flatpak run org.mozilla.firefox %u"), None);
+    }
+
+    #[test]
+    fn test_inventory() {
+        let inventory = Inventory::from_entries(&entries());
+        assert_eq!(inventory.browsers, vec!["chromium", "firefox"]);
+        assert_eq!(inventory.clients, vec![vec!["slack"], vec!["thunderbird"]]);
+    }
+
+    #[test]
+    fn test_render_suggested_config_parses() {
+        let inventory = Inventory::from_entries(&entries());
+        let rendered = render_config(&inventory, &Choices::suggested(&inventory));
+
+        assert!(rendered.contains("# Installed browsers: chromium, firefox\n"));
+        assert!(rendered.contains("# [chat]\n# browser = \"chromium\"\n# clients = [\"slack\"]\n"));
+
+        let config: Config = toml::from_str(&rendered).unwrap();
+        assert_eq!(config.default.browser, "chromium");
+        assert!(config.sections.is_empty());
+    }
+
+    #[test]
+    fn test_prompt_choices() {
+        let inventory = Inventory::from_entries(&entries());
+        let mut input = io::Cursor::new("2\n9\n1\n\n");
+        let mut output = Vec::new();
+
+        let choices = Choices::prompt(&inventory, &mut input, &mut output).unwrap();
+        assert_eq!(choices.default, "firefox");
+        assert_eq!(choices.groups, vec![Some("chromium".to_string()), None]);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("Please enter a number from the list."));
+
+        let config: Config = toml::from_str(&render_config(&inventory, &choices)).unwrap();
+        let chat = config.sections.get("chat").unwrap();
+        assert_eq!(chat.browser, "chromium");
+        assert_eq!(chat.clients, vec!["slack"]);
+    }
+
+    #[test]
+    fn test_prompt_end_of_input_keeps_suggestions() {
+        let inventory = Inventory::from_entries(&entries());
+        let mut input = io::Cursor::new("");
+        let choices = Choices::prompt(&inventory, &mut input, &mut Vec::new()).unwrap();
+        assert_eq!(choices.default, "chromium");
+        assert_eq!(choices.groups, vec![None, None]);
+    }
+
+    #[test]
+    fn test_scan_applications_shadowing() {
+        let home = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        fs::write(
+            home.path().join("firefox.desktop"),
+            "[Desktop Entry]\nHidden=true\n",
+        )
+        .unwrap();
+        fs::write(
+            system.path().join("firefox.desktop"),
+            "[Desktop Entry]\nExec=firefox\nMimeType=x-scheme-handler/http;\n",
+        )
+        .unwrap();
+        fs::write(
+            system.path().join("chromium.desktop"),
+            "[Desktop Entry]\nExec=chromium\nMimeType=x-scheme-handler/http;\n",
+        )
+        .unwrap();
+
+        let entries = scan_applications(&[home.path().into(), system.path().into()]);
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["chromium"]);
+    }
+}
@@ -0,0 +1,516 @@
+//! Registration as the default browser for `hyprchoosy install` and `uninstall`.
+//!
+//! This module writes the `hyprchoosy.desktop` entry and edits `mimeapps.list`
+//! directly, the way `xdg-mime default` does, keeping the previous defaults so
+//! they can be put back on uninstall.
+
+use crate::config::{
+    load_config, xdg_config_home, xdg_data_home, xdg_state_home, Config, WEB_SCHEMES,
+};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "debug")]
+use log::{debug, info};
+
+pub const DESKTOP_ID: &str = "hyprchoosy.desktop";
+
+/// MIME types hyprchoosy always registers itself as the default handler for.
+pub const MIME_TYPES: &[&str] = &[
+    "x-scheme-handler/http",
+    "x-scheme-handler/https",
+    "text/html",
+];
+
+/// Non-web schemes the rules in `cfg` handle, such as `mailto`, sorted.
+pub fn configured_schemes(cfg: &Config) -> Vec<String> {
+    let mut schemes: Vec<String> = cfg
+        .sections
+        .values()
+        .flat_map(|sec| &sec.schemes)
+        .map(|scheme| scheme.to_ascii_lowercase())
+        .filter(|scheme| !WEB_SCHEMES.contains(&scheme.as_str()))
+        .collect();
+    schemes.sort();
+    schemes.dedup();
+    schemes
+}
+
+/// [`MIME_TYPES`] followed by a scheme handler for each of `schemes`.
+fn mime_types(schemes: &[String]) -> Vec<String> {
+    let handlers = schemes
+        .iter()
+        .map(|scheme| format!("x-scheme-handler/{}", scheme));
+    let mut mimes: Vec<String> = MIME_TYPES.iter().map(|m| m.to_string()).collect();
+    for mime in handlers {
+        if !mimes.contains(&mime) {
+            mimes.push(mime);
+        }
+    }
+    mimes
+}
+
+const DEFAULTS_GROUP: &str = "[Default Applications]";
+
+/// Previous handlers by MIME type; `None` when there was no default before.
+type PreviousDefaults = BTreeMap<String, Option<String>>;
+
+/// The XDG base directories install touches.
+#[derive(Debug, Clone)]
+pub struct XdgDirs {
+    pub config_home: PathBuf,
+    pub data_home: PathBuf,
+    pub state_home: PathBuf,
+}
+
+impl XdgDirs {
+    pub fn from_env() -> Self {
+        Self {
+            config_home: xdg_config_home(),
+            data_home: xdg_data_home(),
+            state_home: xdg_state_home(),
+        }
+    }
+
+    pub fn applications_dir(&self) -> PathBuf {
+        self.data_home.join("applications")
+    }
+
+    pub fn desktop_file(&self) -> PathBuf {
+        self.applications_dir().join(DESKTOP_ID)
+    }
+
+    pub fn mimeapps_list(&self) -> PathBuf {
+        self.config_home.join("mimeapps.list")
+    }
+
+    fn previous_defaults(&self) -> PathBuf {
+        self.state_home.join("hyprchoosy/previous-defaults.json")
+    }
+}
+
+/// The lines of a `mimeapps.list`, edited in place so other entries survive.
+#[derive(Debug, Default)]
+struct MimeApps {
+    lines: Vec<String>,
+}
+
+impl MimeApps {
+    fn parse(contents: &str) -> Self {
+        Self {
+            lines: contents.lines().map(String::from).collect(),
+        }
+    }
+
+    /// Line range of the `[Default Applications]` group, header excluded.
+    fn defaults_group(&self) -> Option<(usize, usize)> {
+        let header = self.lines.iter().position(|l| l.trim() == DEFAULTS_GROUP)?;
+        let end = self.lines[header + 1..]
+            .iter()
+            .position(|l| l.trim_start().starts_with('['))
+            .map_or(self.lines.len(), |i| header + 1 + i);
+        Some((header + 1, end))
+    }
+
+    fn find(&self, mime: &str) -> Option<usize> {
+        let (start, end) = self.defaults_group()?;
+        (start..end).find(|&i| {
+            self.lines[i]
+                .split_once('=')
+                .is_some_and(|(key, _)| key.trim() == mime)
+        })
+    }
+
+    fn get(&self, mime: &str) -> Option<&str> {
+        let line = &self.lines[self.find(mime)?];
+        line.split_once('=').map(|(_, value)| value.trim())
+    }
+
+    /// Sets the default for `mime`, or removes it when `value` is `None`.
+    fn set(&mut self, mime: &str, value: Option<&str>) {
+        match (self.find(mime), value) {
+            (Some(i), Some(value)) => self.lines[i] = format!("{}={}", mime, value),
+            (Some(i), None) => {
+                self.lines.remove(i);
+            }
+            (None, Some(value)) => {
+                let end = match self.defaults_group() {
+                    Some((_, end)) => end,
+                    None => {
+                        if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                            self.lines.push(String::new());
+                        }
+                        self.lines.push(DEFAULTS_GROUP.to_string());
+                        self.lines.len()
+                    }
+                };
+                // Keep the blank line separating this group from the next one.
+                let at = (0..end)
+                    .rev()
+                    .find(|&i| !self.lines[i].trim().is_empty())
+                    .map_or(end, |i| i + 1);
+                self.lines.insert(at, format!("{}={}", mime, value));
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = self.lines.join("\n");
+        out.push('\n');
+        out
+    }
+}
+
+/// Whether `value` (a `;`-separated list) names hyprchoosy first.
+fn is_hyprchoosy(value: Option<&str>) -> bool {
+    value
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|id| id.trim() == DESKTOP_ID)
+}
+
+fn desktop_entry(exec: &Path, mimes: &[String]) -> String {
+    let exec = exec.display().to_string();
+    let exec = if exec.contains([' ', '"', '\\', '$', '`']) {
+        let escaped = exec
+            .replace('\\', "\\\\\\\\")
+            .replace('"', "\\\\\"")
+            .replace('$', "\\\\$")
+            .replace('`', "\\\\`");
+        format!("\"{}\"", escaped)
+    } else {
+        exec
+    };
+    format!(
+        "[Desktop Entry]
+Version=1.0
+Name=Hyprchoosy
+Comment=Smart browser router for Hyprland
+Exec={} %U
+Terminal=false
+Type=Application
+MimeType={};
+Categories=Network;
+NoDisplay=true
+",
+        exec,
+        mimes.join(";")
+    )
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn remove_optional(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn load_mimeapps(dirs: &XdgDirs) -> Result<MimeApps> {
+    Ok(read_optional(&dirs.mimeapps_list())?
+        .map(|data| MimeApps::parse(&data))
+        .unwrap_or_default())
+}
+
+fn load_previous(dirs: &XdgDirs) -> Result<PreviousDefaults> {
+    let path = dirs.previous_defaults();
+    match read_optional(&path)? {
+        Some(data) => serde_json::from_str(&data)
+            .with_context(|| format!("Invalid previous defaults in {}", path.display())),
+        None => Ok(PreviousDefaults::new()),
+    }
+}
+
+/// Writes the desktop entry and makes it the default for [`MIME_TYPES`] and
+/// the handler of each of `schemes`.
+///
+/// Running it again only refreshes the desktop entry and adds new schemes: a
+/// default that already points at hyprchoosy is never recorded as the one to
+/// restore.
+pub fn install(dirs: &XdgDirs, exec: &Path, schemes: &[String]) -> Result<()> {
+    let mimes = mime_types(schemes);
+    write_file(&dirs.desktop_file(), &desktop_entry(exec, &mimes))?;
+
+    let mut mimeapps = load_mimeapps(dirs)?;
+    let mut previous = load_previous(dirs)?;
+
+    for mime in &mimes {
+        let current = mimeapps.get(mime);
+        if !is_hyprchoosy(current) {
+            #[cfg(feature = "debug")]
+            debug!("Previous default for {}: {:?}", mime, current);
+            previous.insert(mime.to_string(), current.map(String::from));
+        }
+        mimeapps.set(mime, Some(DESKTOP_ID));
+    }
+
+    write_file(
+        &dirs.previous_defaults(),
+        &serde_json::to_string_pretty(&previous)?,
+    )?;
+    write_file(&dirs.mimeapps_list(), &mimeapps.render())?;
+
+    #[cfg(feature = "debug")]
+    info!("Installed {}", dirs.desktop_file().display());
+    Ok(())
+}
+
+/// Restores the previous defaults and removes the desktop entry.
+///
+/// Covers every MIME type install recorded, plus [`MIME_TYPES`] and `schemes`.
+/// Defaults changed to something else since install are left alone.
+pub fn uninstall(dirs: &XdgDirs, schemes: &[String]) -> Result<()> {
+    let mut mimeapps = load_mimeapps(dirs)?;
+    let previous = load_previous(dirs)?;
+    let mut changed = false;
+
+    let mut mimes = mime_types(schemes);
+    for mime in previous.keys() {
+        if !mimes.contains(mime) {
+            mimes.push(mime.clone());
+        }
+    }
+    for mime in &mimes {
+        if is_hyprchoosy(mimeapps.get(mime)) {
+            let restored = previous.get(mime).cloned().flatten();
+            #[cfg(feature = "debug")]
+            debug!("Restoring default for {}: {:?}", mime, restored);
+            mimeapps.set(mime, restored.as_deref());
+            changed = true;
+        }
+    }
+
+    if changed {
+        write_file(&dirs.mimeapps_list(), &mimeapps.render())?;
+    }
+    remove_optional(&dirs.previous_defaults())?;
+    remove_optional(&dirs.desktop_file())?;
+
+    #[cfg(feature = "debug")]
+    info!("Uninstalled {}", dirs.desktop_file().display());
+    Ok(())
+}
+
+/// Refreshes the desktop database if `update-desktop-database` is installed.
+fn update_desktop_database(dirs: &XdgDirs) {
+    let _status = Command::new("update-desktop-database")
+        .arg(dirs.applications_dir())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    #[cfg(feature = "debug")]
+    debug!("update-desktop-database: {:?}", _status);
+}
+
+/// Schemes from the config, or none when there is no usable config yet.
+fn schemes_from_config() -> Vec<String> {
+    load_config()
+        .map(|cfg| configured_schemes(&cfg))
+        .unwrap_or_default()
+}
+
+/// Registers the running executable as the default browser and as the
+/// handler of the schemes the config's rules list.
+///
+/// Returns the directories written to and the extra schemes registered.
+pub fn run_install() -> Result<(XdgDirs, Vec<String>)> {
+    let exec = std::env::current_exe().context("Failed to locate the hyprchoosy executable")?;
+    let dirs = XdgDirs::from_env();
+    let schemes = schemes_from_config();
+    install(&dirs, &exec, &schemes)?;
+    update_desktop_database(&dirs);
+    Ok((dirs, schemes))
+}
+
+/// Puts back the defaults that were in place before `run_install`.
+pub fn run_uninstall() -> Result<XdgDirs> {
+    let dirs = XdgDirs::from_env();
+    uninstall(&dirs, &schemes_from_config())?;
+    update_desktop_database(&dirs);
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs(root: &Path) -> XdgDirs {
+        XdgDirs {
+            config_home: root.join("config"),
+            data_home: root.join("data"),
+            state_home: root.join("state"),
+        }
+    }
+
+    const MIMEAPPS: &str = "[Added Associations]
+text/html=firefox.desktop;
+
+[Default Applications]
+text/html=firefox.desktop
+x-scheme-handler/http=firefox.desktop
+image/png=imv.desktop
+
+[Removed Associations]
+";
+
+    #[test]
+    fn test_install_sets_defaults_and_keeps_other_entries() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = dirs(root.path());
+        write_file(&dirs.mimeapps_list(), MIMEAPPS).unwrap();
+
+        install(&dirs, Path::new("/usr/bin/hyprchoosy"), &[]).unwrap();
+
+        let desktop = fs::read_to_string(dirs.desktop_file()).unwrap();
+        assert!(desktop.contains("Exec=/usr/bin/hyprchoosy %U\n"));
+        assert!(
+            desktop.contains("MimeType=x-scheme-handler/http;x-scheme-handler/https;text/html;")
+        );
+
+        let list = fs::read_to_string(dirs.mimeapps_list()).unwrap();
+        assert_eq!(
+            list,
+            "[Added Associations]
+text/html=firefox.desktop;
+
+[Default Applications]
+text/html=hyprchoosy.desktop
+x-scheme-handler/http=hyprchoosy.desktop
+image/png=imv.desktop
+x-scheme-handler/https=hyprchoosy.desktop
+
+[Removed Associations]
+"
+        );
+    }
+
+    #[test]
+    fn test_install_is_idempotent_and_uninstall_restores() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = dirs(root.path());
+        write_file(&dirs.mimeapps_list(), MIMEAPPS).unwrap();
+
+        install(&dirs, Path::new("/usr/bin/hyprchoosy"), &[]).unwrap();
+        install(&dirs, Path::new("/usr/bin/hyprchoosy"), &[]).unwrap();
+        uninstall(&dirs, &[]).unwrap();
+
+        let list = fs::read_to_string(dirs.mimeapps_list()).unwrap();
+        assert_eq!(list, MIMEAPPS);
+        assert!(!dirs.desktop_file().exists());
+        assert!(!dirs.previous_defaults().exists());
+
+        // Nothing left to undo.
+        uninstall(&dirs, &[]).unwrap();
+        assert_eq!(fs::read_to_string(dirs.mimeapps_list()).unwrap(), MIMEAPPS);
+    }
+
+    #[test]
+    fn test_install_without_mimeapps_list() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = dirs(root.path());
+
+        install(&dirs, Path::new("/opt/my apps/hyprchoosy"), &[]).unwrap();
+        let list = fs::read_to_string(dirs.mimeapps_list()).unwrap();
+        assert_eq!(
+            list,
+            "[Default Applications]
+x-scheme-handler/http=hyprchoosy.desktop
+x-scheme-handler/https=hyprchoosy.desktop
+text/html=hyprchoosy.desktop
+"
+        );
+        let desktop = fs::read_to_string(dirs.desktop_file()).unwrap();
+        assert!(desktop.contains("Exec=\"/opt/my apps/hyprchoosy\" %U\n"));
+
+        uninstall(&dirs, &[]).unwrap();
+        let list = fs::read_to_string(dirs.mimeapps_list()).unwrap();
+        assert_eq!(list, "[Default Applications]\n");
+    }
+
+    #[test]
+    fn test_uninstall_keeps_defaults_changed_since_install() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = dirs(root.path());
+        write_file(&dirs.mimeapps_list(), MIMEAPPS).unwrap();
+
+        install(&dirs, Path::new("/usr/bin/hyprchoosy"), &[]).unwrap();
+        let mut mimeapps = load_mimeapps(&dirs).unwrap();
+        mimeapps.set("text/html", Some("chromium.desktop"));
+        write_file(&dirs.mimeapps_list(), &mimeapps.render()).unwrap();
+
+        uninstall(&dirs, &[]).unwrap();
+        let mimeapps = load_mimeapps(&dirs).unwrap();
+        assert_eq!(mimeapps.get("text/html"), Some("chromium.desktop"));
+        assert_eq!(
+            mimeapps.get("x-scheme-handler/http"),
+            Some("firefox.desktop")
+        );
+        assert_eq!(mimeapps.get("x-scheme-handler/https"), None);
+    }
+
+    #[test]
+    fn test_configured_schemes() {
+        let cfg: Config = toml::from_str(
+            r#"
+[work-mail]
+browser = "thunderbird"
+schemes = ["mailto", "https"]
+
+[mail]
+browser = "geary"
+schemes = ["MAILTO", "webcal"]
+
+[web]
+browser = "firefox"
+url = ["example.com"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(configured_schemes(&cfg), vec!["mailto", "webcal"]);
+    }
+
+    #[test]
+    fn test_install_registers_schemes_and_uninstall_removes_them() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = dirs(root.path());
+        write_file(&dirs.mimeapps_list(), MIMEAPPS).unwrap();
+
+        let schemes = ["mailto".to_string()];
+        install(&dirs, Path::new("/usr/bin/hyprchoosy"), &schemes).unwrap();
+
+        let desktop = fs::read_to_string(dirs.desktop_file()).unwrap();
+        assert!(desktop.contains(
+            "MimeType=x-scheme-handler/http;x-scheme-handler/https;text/html;x-scheme-handler/mailto;"
+        ));
+        let mimeapps = load_mimeapps(&dirs).unwrap();
+        assert_eq!(
+            mimeapps.get("x-scheme-handler/mailto"),
+            Some("hyprchoosy.desktop")
+        );
+
+        // The scheme was since removed from the config; uninstall still undoes it.
+        uninstall(&dirs, &[]).unwrap();
+        assert_eq!(fs::read_to_string(dirs.mimeapps_list()).unwrap(), MIMEAPPS);
+    }
+}
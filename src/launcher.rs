@@ -3,22 +3,114 @@
 //! This module handles spawning browser processes in a detached manner
 //! so they continue running after hyprchoosy exits.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
 use std::process::Command;
+use std::thread;
 
 #[cfg(feature = "debug")]
 use log::{info, warn};
 
+/// Focus tokens hyprchoosy was started with.
+///
+/// Wayland xdg-activation and X11 startup notification are separate protocols,
+/// so each value is only ever handed on under its own variable.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivationToken {
+    /// `XDG_ACTIVATION_TOKEN`, for Wayland compositors.
+    pub xdg_activation: Option<String>,
+    /// `DESKTOP_STARTUP_ID`, for X11 startup notification.
+    pub startup_id: Option<String>,
+}
+
+impl ActivationToken {
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let var = |name| var(name).filter(|value| !value.is_empty());
+        let token = ActivationToken {
+            xdg_activation: var("XDG_ACTIVATION_TOKEN"),
+            startup_id: var("DESKTOP_STARTUP_ID"),
+        };
+        (token != ActivationToken::default()).then_some(token)
+    }
+}
+
+/// Returns the activation tokens hyprchoosy was started with, if any.
+///
+/// They are handed on to the browser so the compositor lets it take focus
+/// instead of treating the new window as focus stealing.
+pub fn activation_token() -> Option<ActivationToken> {
+    ActivationToken::from_vars(|name| env::var(name).ok())
+}
+
+fn apply_activation_token(cmd: &mut Command, token: Option<&ActivationToken>) {
+    let Some(token) = token else { return };
+    #[cfg(feature = "debug")]
+    info!("Passing activation token to browser: {:?}", token);
+    if let Some(value) = &token.xdg_activation {
+        cmd.env("XDG_ACTIVATION_TOKEN", value);
+    }
+    if let Some(value) = &token.startup_id {
+        cmd.env("DESKTOP_STARTUP_ID", value);
+    }
+}
+
 pub fn launch_browser(browser: &str, url: &str) -> Result<()> {
+    launch_browser_with_token(browser, url, activation_token().as_ref())
+}
+
+/// Like [`launch_browser`], but hands on `token` instead of our own activation token.
+///
+/// The daemon uses this to pass along the token the forwarding shim was started with.
+pub fn launch_browser_with_token(
+    browser: &str,
+    url: &str,
+    token: Option<&ActivationToken>,
+) -> Result<()> {
+    launch_browser_urls(browser, &[url.to_string()], token)
+}
+
+/// Opens all of `urls` with a single `browser` process, one tab each.
+pub fn launch_browser_urls(
+    browser: &str,
+    urls: &[String],
+    token: Option<&ActivationToken>,
+) -> Result<()> {
     #[cfg(feature = "debug")]
-    info!("Launching browser: '{}' with URL: '{}'", browser, url);
+    info!("Launching browser: '{}' with URLs: {:?}", browser, urls);
+
+    let mut cmd = Command::new(browser);
+    cmd.args(urls);
+    spawn_detached(cmd, "browser", browser, token)
+}
+
+/// Runs `argv` detached, the same way a browser is launched.
+pub fn launch_command(argv: &[String], token: Option<&ActivationToken>) -> Result<()> {
+    let Some((program, args)) = argv.split_first() else {
+        bail!("Command is empty");
+    };
+
+    #[cfg(feature = "debug")]
+    info!("Launching command: '{}' with args: {:?}", program, args);
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    spawn_detached(cmd, "command", program, token)
+}
+
+/// Spawns `cmd` in its own session so it outlives hyprchoosy.
+fn spawn_detached(
+    mut cmd: Command,
+    kind: &str,
+    program: &str,
+    token: Option<&ActivationToken>,
+) -> Result<()> {
+    apply_activation_token(&mut cmd, token);
 
     #[cfg(unix)]
     {
         use std::os::unix::process::CommandExt;
-        let mut cmd = Command::new(browser);
-        cmd.arg(url)
-            .stdin(std::process::Stdio::null())
+        cmd.stdin(std::process::Stdio::null())
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null());
         unsafe {
@@ -27,32 +119,79 @@ pub fn launch_browser(browser: &str, url: &str) -> Result<()> {
                 Ok(())
             });
         }
-        match cmd.spawn() {
-            Ok(_) => {
-                #[cfg(feature = "debug")]
-                info!("Successfully spawned browser '{}'", browser);
-                Ok(())
-            }
-            Err(e) => {
-                #[cfg(feature = "debug")]
-                warn!("Failed to spawn browser '{}': {}", browser, e);
-                Err(e).with_context(|| format!("Failed to spawn browser '{}'", browser))
-            }
-        }
     }
-    #[cfg(not(unix))]
-    {
-        match Command::new(browser).arg(url).spawn() {
-            Ok(_) => {
-                #[cfg(feature = "debug")]
-                info!("Successfully spawned browser '{}'", browser);
-                Ok(())
-            }
-            Err(e) => {
-                #[cfg(feature = "debug")]
-                warn!("Failed to spawn browser '{}': {}", browser, e);
-                Err(e).with_context(|| format!("Failed to spawn browser '{}'", browser))
-            }
+
+    match cmd.spawn() {
+        Ok(mut child) => {
+            #[cfg(feature = "debug")]
+            info!("Successfully spawned {} '{}'", kind, program);
+            // Collect the exit status so a long-running daemon leaves no zombies.
+            thread::spawn(move || child.wait());
+            Ok(())
+        }
+        Err(e) => {
+            #[cfg(feature = "debug")]
+            warn!("Failed to spawn {} '{}': {}", kind, program, e);
+            Err(e).with_context(|| format!("Failed to spawn {} '{}'", kind, program))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn envs(token: Option<&ActivationToken>) -> Vec<(String, Option<String>)> {
+        let mut cmd = Command::new("true");
+        apply_activation_token(&mut cmd, token);
+        cmd.get_envs()
+            .map(|(k, v)| {
+                let k = k.to_string_lossy().into_owned();
+                (k, v.map(OsStr::to_string_lossy).map(Into::into))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tokens_keep_their_protocol() {
+        let wayland = ActivationToken {
+            xdg_activation: Some("wl-1".into()),
+            startup_id: None,
+        };
+        assert_eq!(
+            envs(Some(&wayland)),
+            vec![("XDG_ACTIVATION_TOKEN".into(), Some("wl-1".into()))]
+        );
+
+        let x11 = ActivationToken {
+            xdg_activation: None,
+            startup_id: Some("slack/hyprchoosy/1-0-host_TIME1".into()),
+        };
+        assert_eq!(
+            envs(Some(&x11)),
+            vec![(
+                "DESKTOP_STARTUP_ID".into(),
+                Some("slack/hyprchoosy/1-0-host_TIME1".into())
+            )]
+        );
+        assert!(envs(None).is_empty());
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let token = ActivationToken::from_vars(|name| match name {
+            "XDG_ACTIVATION_TOKEN" => Some("wl-1".into()),
+            "DESKTOP_STARTUP_ID" => Some(String::new()),
+            _ => None,
+        });
+        assert_eq!(
+            token,
+            Some(ActivationToken {
+                xdg_activation: Some("wl-1".into()),
+                startup_id: None,
+            })
+        );
+        assert_eq!(ActivationToken::from_vars(|_| None), None);
+    }
+}
@@ -3,14 +3,33 @@
 //! This library provides functionality to route URLs to different browsers based on
 //! the originating application and URL patterns.
 
+pub mod check;
 pub mod client_detection;
 pub mod config;
+pub mod daemon;
+pub mod dispatch;
+pub mod domain;
+pub mod explain;
+pub mod init;
+pub mod install;
 pub mod launcher;
 pub mod logger;
 pub mod matcher;
+pub mod picker;
+pub mod remember;
+pub mod rewrite;
+pub mod router;
+pub mod stdin;
+pub mod template;
+pub mod unwrap;
 
 pub use client_detection::detect_client;
-pub use config::{load_config, Config, DefaultSection, RuleSection};
-pub use launcher::launch_browser;
+pub use config::{load_config, Action, Config, DefaultSection, RuleSection};
+pub use dispatch::{dispatch, dispatch_all};
+pub use launcher::{activation_token, launch_browser, launch_browser_with_token, ActivationToken};
 pub use logger::init_logger;
-pub use matcher::{match_client, match_host, parse_url_host};
+pub use matcher::{
+    clean_url, is_excluded, match_client, match_client_for, match_host, match_host_for,
+    match_scheme, normalize_host, parse_url_host, parse_url_scheme,
+};
+pub use router::{choose_browser, route, route_with, Overrides, Route};
@@ -1,30 +1,177 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use hyprchoosy::init::{self, InitOptions};
 use hyprchoosy::{
-    detect_client, init_logger, launch_browser, load_config, match_client, match_host,
-    parse_url_host,
+    activation_token, check, daemon, detect_client, dispatch_all, explain, init_logger, install,
+    load_config, remember, stdin, Overrides,
 };
-use std::env;
+use std::process;
 
 #[cfg(feature = "debug")]
 use log::info;
 
+const USAGE: &str = "Usage: hyprchoosy [OPTIONS] <URL>...
+       hyprchoosy [OPTIONS] - | --stdin
+       hyprchoosy explain [OPTIONS] <URL>
+       hyprchoosy daemon
+       hyprchoosy config check [--strict]
+       hyprchoosy init [--non-interactive] [--force]
+       hyprchoosy install
+       hyprchoosy uninstall
+       hyprchoosy remember <HOST> <BROWSER|SECTION>
+
+Options:
+  --client <NAME>   Route as if the link came from NAME instead of detecting it
+  --browser <NAME>  Open the link in NAME, whatever the rules say
+  --section <NAME>  Use the [NAME] rule, or [default], instead of matching";
+
+/// Subcommands that don't open or explain links.
+const SUBCOMMANDS: &[&str] = &[
+    "daemon",
+    "config",
+    "init",
+    "install",
+    "uninstall",
+    "remember",
+];
+
+/// The parsed command line.
+#[derive(Debug, Default)]
+struct Args {
+    positional: Vec<String>,
+    stdin: bool,
+    client: Option<String>,
+    overrides: Overrides,
+    init: InitOptions,
+    strict: bool,
+}
+
+fn parse_args() -> Result<Args, lexopt::Error> {
+    use lexopt::prelude::*;
+
+    let mut args = Args::default();
+    let mut parser = lexopt::Parser::from_env();
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Long("client") => args.client = Some(parser.value()?.string()?),
+            Long("browser") => args.overrides.browser = Some(parser.value()?.string()?),
+            Long("section") => args.overrides.section = Some(parser.value()?.string()?),
+            Long("stdin") => args.stdin = true,
+            Long("non-interactive") => args.init.non_interactive = true,
+            Long("force") => args.init.force = true,
+            Long("strict") => args.strict = true,
+            Short('h') | Long("help") => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            Value(value) if value == "-" => args.stdin = true,
+            Value(value) => args.positional.push(value.string()?),
+            _ => return Err(arg.unexpected()),
+        }
+    }
+    Ok(args)
+}
+
 fn main() -> Result<()> {
     init_logger()?;
 
     #[cfg(feature = "debug")]
     info!("=== Starting hyprchoosy ===");
 
-    let url = env::args().nth(1).unwrap_or_default();
+    let args = parse_args().map_err(|e| anyhow!("{}\n{}", e, USAGE))?;
+    let positional: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    let subcommand = positional.first().filter(|p| SUBCOMMANDS.contains(p));
+
+    if subcommand.is_some() && (args.stdin || args.client.is_some() || !args.overrides.is_empty()) {
+        bail!(
+            "--client, --browser, --section and --stdin only apply when opening or explaining links\n{}",
+            USAGE
+        );
+    }
+    if subcommand != Some(&"init") && (args.init.force || args.init.non_interactive) {
+        bail!(
+            "--force and --non-interactive only apply to init\n{}",
+            USAGE
+        );
+    }
+
+    if args.strict && positional != ["config", "check"] {
+        bail!("--strict only applies to config check\n{}", USAGE);
+    }
+
+    let input = match (positional.as_slice(), args.stdin) {
+        (["daemon"], _) => {
+            #[cfg(feature = "debug")]
+            info!("Starting daemon");
+            return daemon::run();
+        }
+        (["explain", url], false) => return explain::run(url, args.client, &args.overrides),
+        (["config", "check"], _) => {
+            if !check::run(args.strict)? {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        (["init"], _) => {
+            let path = init::run(args.init)?;
+            println!("Wrote {}", path.display());
+            return Ok(());
+        }
+        (["install"], _) => {
+            let (dirs, schemes) = install::run_install()?;
+            println!(
+                "Installed {} as the default browser in {}",
+                dirs.desktop_file().display(),
+                dirs.mimeapps_list().display()
+            );
+            if !schemes.is_empty() {
+                println!("Also handling {} links", schemes.join(", "));
+            }
+            return Ok(());
+        }
+        (["uninstall"], _) => {
+            let dirs = install::run_uninstall()?;
+            println!(
+                "Restored the previous default browser in {}",
+                dirs.mimeapps_list().display()
+            );
+            return Ok(());
+        }
+        (["remember", host, target], _) => {
+            let (host, section) = remember::run(host, target)?;
+            println!("{} now opens with [{}]", host, section);
+            return Ok(());
+        }
+        ([], true) => stdin::read()?,
+        (urls, false) if subcommand.is_none() && urls.first() != Some(&"explain") => stdin::Input {
+            urls: urls.iter().map(|url| url.to_string()).collect(),
+            client: None,
+        },
+        _ => bail!(USAGE),
+    };
+    let client = args.client.or(input.client);
+    let urls = input.urls;
 
     #[cfg(feature = "debug")]
-    info!("Received URL: '{}'", url);
+    info!("Received URLs: {:?}", urls);
 
-    if url.is_empty() {
+    if urls.is_empty() {
         #[cfg(feature = "debug")]
         log::error!("No URL provided");
-        bail!("Usage: hyprchoosy <URL>");
+        bail!(USAGE);
+    }
+
+    let request = daemon::Request {
+        client: client.clone(),
+        overrides: args.overrides.clone(),
+        ..daemon::Request::new(&urls)
+    };
+    if let Some(result) = daemon::forward(&request) {
+        return result;
     }
 
+    #[cfg(feature = "debug")]
+    info!("No daemon running, routing in-process");
+
     #[cfg(feature = "debug")]
     info!("Loading configuration...");
     let cfg = load_config()?;
@@ -32,41 +179,15 @@ fn main() -> Result<()> {
     #[cfg(feature = "debug")]
     info!("Configuration loaded successfully");
 
-    let host = parse_url_host(&url)?;
-    #[cfg(feature = "debug")]
-    info!("Extracted host: '{}'", host);
-
-    let client = detect_client();
+    let client = client.or_else(|| detect_client(&cfg));
     #[cfg(feature = "debug")]
     info!("Detected client: {:?}", client);
 
-    if let Some(c) = client.as_deref() {
-        #[cfg(feature = "debug")]
-        info!("Checking client rules for '{}'", c);
-
-        if let Some(sec) = match_client(c, &cfg.sections) {
-            #[cfg(feature = "debug")]
-            info!("Using browser from client rule: '{}'", sec.browser);
-            return launch_browser(&sec.browser, &url);
-        }
-    } else {
-        #[cfg(feature = "debug")]
-        info!("No client detected, skipping client rules");
-    }
-
-    #[cfg(feature = "debug")]
-    info!("Checking host rules for '{}'", host);
-
-    if let Some(sec) = match_host(&host, &cfg.sections) {
-        #[cfg(feature = "debug")]
-        info!("Using browser from host rule: '{}'", sec.browser);
-        return launch_browser(&sec.browser, &url);
-    }
-
-    #[cfg(feature = "debug")]
-    info!(
-        "No rules matched, using default browser: '{}'",
-        cfg.default.browser
-    );
-    launch_browser(&cfg.default.browser, &url)
+    dispatch_all(
+        &cfg,
+        &urls,
+        client.as_deref(),
+        &args.overrides,
+        activation_token().as_ref(),
+    )
 }
@@ -3,59 +3,276 @@
 //! This module provides functions to match URLs and clients against
 //! configured rules to determine which browser should be used.
 
-use crate::config::RuleSection;
+use crate::config::{Config, HostPrivacy, PrivacySection, RuleSection};
+use crate::domain::registrable_domain;
+use crate::unwrap::unwrap_url;
 use anyhow::{Context, Result};
+use ipnet::IpNet;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
 use url::Url;
 
 #[cfg(feature = "debug")]
 use log::{debug, info};
 
-pub fn parse_url_host(u: &str) -> Result<String> {
-    let parsed = if u.contains("://") {
+/// Schemes that are always followed by a scheme-specific part, never a port.
+const OPAQUE_SCHEMES: &[&str] = &["mailto", "tel", "sms", "callto", "geo", "magnet", "urn"];
+
+/// Parses `u`, treating a link without a scheme as `http://`.
+///
+/// `mailto:`, `tel:`, `file:///` and custom schemes such as `slack://` or
+/// `spotify:track:...` keep their scheme.
+pub(crate) fn parse_url(u: &str) -> Result<Url, url::ParseError> {
+    if has_scheme(u) {
         Url::parse(u)
     } else {
         Url::parse(&format!("http://{}", u))
     }
-    .with_context(|| format!("Invalid URL: {}", u))?;
+}
+
+/// Tells `scheme:rest` apart from `host:port`.
+fn has_scheme(u: &str) -> bool {
+    if u.contains("://") {
+        return true;
+    }
+    let Some((scheme, rest)) = u.split_once(':') else {
+        return false;
+    };
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-');
+    valid
+        && (OPAQUE_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str())
+            || !rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Returns the lowercase scheme of `u`, or an error if it isn't a valid link.
+pub fn parse_url_scheme(u: &str) -> Result<String> {
+    let parsed = parse_url(u).with_context(|| format!("Invalid URL: {}", u))?;
+    Ok(parsed.scheme().to_string())
+}
+
+/// Tracking parameters stripped when `[privacy] strip_params` is on.
+pub const DEFAULT_STRIP_PARAMS: &[&str] = &[
+    "utm_*",
+    "fbclid",
+    "gclid",
+    "gclsrc",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "ttclid",
+    "igshid",
+    "mc_eid",
+    "mc_cid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "oly_anon_id",
+    "oly_enc_id",
+    "vero_id",
+    "si",
+];
+
+/// Prepares an incoming link for routing and launching.
+///
+/// Unwraps redirect wrappers unless `[unwrap] enabled = false`, then strips
+/// tracking parameters when `[privacy] strip_params` is enabled.
+pub fn clean_url(cfg: &Config, url: &str) -> String {
+    let mut cleaned = url.to_string();
+    if cfg.unwrap.enabled {
+        cleaned = unwrap_url(&cleaned, &cfg.unwrap.rules);
+    }
+    if cfg.privacy.strip_params {
+        let host = parse_url_host(&cleaned).unwrap_or_default();
+        cleaned = strip_params(&cleaned, &|key| should_strip(&cfg.privacy, &host, key));
+    }
+
+    #[cfg(feature = "debug")]
+    if cleaned != url {
+        info!("Cleaned URL: '{}' -> '{}'", url, cleaned);
+    }
+    cleaned
+}
 
-    Ok(parsed
-        .host_str()
-        .map(|s| s.to_lowercase())
-        .unwrap_or_default())
+fn param_matches(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key
+            .get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+        None => key.eq_ignore_ascii_case(pattern),
+    }
+}
+
+fn should_strip(privacy: &PrivacySection, host: &str, key: &str) -> bool {
+    let overrides: Vec<&HostPrivacy> = privacy
+        .hosts
+        .iter()
+        .filter(|(pattern, _)| {
+            let p = normalize_host(pattern);
+            host == p || host.ends_with(&format!(".{}", p))
+        })
+        .map(|(_, o)| o)
+        .collect();
+
+    if overrides
+        .iter()
+        .any(|o| o.keep.iter().any(|p| param_matches(p, key)))
+    {
+        return false;
+    }
+    DEFAULT_STRIP_PARAMS
+        .iter()
+        .copied()
+        .chain(privacy.params.iter().map(String::as_str))
+        .chain(
+            overrides
+                .iter()
+                .flat_map(|o| o.strip.iter().map(String::as_str)),
+        )
+        .any(|p| param_matches(p, key))
+}
+
+/// Drops query parameters whose decoded name `strip` accepts.
+///
+/// The remaining parameters keep their original encoding and order.
+fn strip_params(url: &str, strip: &dyn Fn(&str) -> bool) -> String {
+    let (before_fragment, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url, None),
+    };
+    let Some((base, query)) = before_fragment.split_once('?') else {
+        return url.to_string();
+    };
+
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            let key = url::form_urlencoded::parse(key.as_bytes())
+                .next()
+                .map(|(k, _)| k.into_owned())
+                .unwrap_or_default();
+            !pair.is_empty() && !strip(&key)
+        })
+        .collect();
+
+    let mut out = base.to_string();
+    if !kept.is_empty() {
+        out.push('?');
+        out.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(fragment);
+    }
+    out
+}
+
+/// Normalizes a host or `url` pattern so both compare equal for the same domain.
+///
+/// Domains are lowercased and converted to punycode (`münchen.de` becomes
+/// `xn--mnchen-3ya.de`), and a trailing dot is dropped. IPv6 addresses are
+/// bracketed like in a URL.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    if let Ok(ip) = host.parse::<Ipv6Addr>() {
+        return format!("[{}]", ip);
+    }
+    match url::Host::parse(host) {
+        Ok(url::Host::Domain(domain)) => domain,
+        Ok(ip) => ip.to_string(),
+        Err(_) => host.to_lowercase(),
+    }
 }
 
+/// Parses a `cidr` entry: a network like `10.0.0.0/8` or a single address.
+pub(crate) fn parse_cidr(cidr: &str) -> Option<IpNet> {
+    let cidr = cidr.trim();
+    cidr.parse::<IpNet>()
+        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+        .ok()
+}
+
+/// Returns the address of an IP host such as `10.1.2.3` or `[::1]`.
+fn host_ip(host: &str) -> Option<IpAddr> {
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    host.parse().ok()
+}
+
+/// Returns the normalized host of `u`; for `mailto:` links, the first recipient's domain.
+///
+/// Links without a host, such as `tel:` or `file:///`, give an empty host.
+pub fn parse_url_host(u: &str) -> Result<String> {
+    let parsed = parse_url(u).with_context(|| format!("Invalid URL: {}", u))?;
+
+    if parsed.scheme() == "mailto" {
+        let domain = parsed
+            .path()
+            .split(',')
+            .next()
+            .and_then(|to| to.rsplit_once('@'))
+            .map(|(_, domain)| normalize_host(&domain.to_lowercase()));
+        return Ok(domain.unwrap_or_default());
+    }
+
+    Ok(parsed.host_str().map(normalize_host).unwrap_or_default())
+}
+
+/// Matches `client` against the client rules of sections that handle web links.
 pub fn match_client<'a>(
     client: &str,
     sections: &'a HashMap<String, RuleSection>,
 ) -> Option<&'a RuleSection> {
+    match_client_for(client, "https", sections).map(|(_, sec)| sec)
+}
+
+/// Matches `client` against the client rules of sections that handle `scheme`,
+/// returning the matched section with its name.
+///
+/// `sections` is usually `&cfg.sections`, or a filtered view of it.
+pub fn match_client_for<'a>(
+    client: &str,
+    scheme: &str,
+    sections: impl IntoIterator<Item = (&'a String, &'a RuleSection)>,
+) -> Option<(&'a String, &'a RuleSection)> {
     #[cfg(feature = "debug")]
     debug!("Matching client: '{}'", client);
 
-    let c = client.to_lowercase();
+    let sections = for_scheme(sections, scheme);
 
     #[cfg(feature = "debug")]
     debug!(
         "Available sections: {:?}",
-        sections.keys().collect::<Vec<_>>()
+        sections.iter().map(|(name, _)| name).collect::<Vec<_>>()
     );
 
-    for (_section_name, sec) in sections.iter() {
+    for (name, sec) in sections {
         #[cfg(feature = "debug")]
         debug!(
             "  Checking section '{}' with clients: {:?}",
-            _section_name, sec.clients
+            name, sec.clients
         );
 
         for needle in &sec.clients {
-            let needle_lower = needle.to_lowercase();
-            if c.contains(&needle_lower) {
+            if needle.matches(client) {
                 #[cfg(feature = "debug")]
                 info!(
                     "Client '{}' matched rule '{}' (pattern: '{}')",
-                    client, _section_name, needle
+                    client, name, needle
                 );
-                return Some(sec);
+                return Some((name, sec));
             }
         }
     }
@@ -65,48 +282,259 @@ pub fn match_client<'a>(
     None
 }
 
+/// Matches `host` against the URL rules of sections that handle web links.
 pub fn match_host<'a>(
     host: &str,
     sections: &'a HashMap<String, RuleSection>,
 ) -> Option<&'a RuleSection> {
+    match_host_for(host, "https", sections).map(|(_, sec)| sec)
+}
+
+/// Matches `host` against the URL rules of sections that handle `scheme`,
+/// returning the matched section with its name.
+///
+/// A `domain` rule wins over a `url` pattern shorter than the host's
+/// registrable domain, so `url = ["github.io"]` in one section doesn't take
+/// links that `domain = ["alice.github.io"]` in another is meant for. `cidr`
+/// rules only apply when no `url` pattern names the IP itself.
+pub fn match_host_for<'a>(
+    host: &str,
+    scheme: &str,
+    sections: impl IntoIterator<Item = (&'a String, &'a RuleSection)>,
+) -> Option<(&'a String, &'a RuleSection)> {
     #[cfg(feature = "debug")]
     debug!("Matching host: '{}'", host);
 
-    let h = host.to_lowercase();
+    let sections = for_scheme(sections, scheme);
+    let h = normalize_host(host);
+    // Only consult the Public Suffix List when some section has `domain` rules.
+    let site = sections
+        .iter()
+        .any(|(_, s)| !s.domain.is_empty())
+        .then(|| registrable_domain(&h))
+        .flatten();
+    let ip = host_ip(&h);
+
+    // The first match of each kind, with the matched `url` pattern.
+    let mut by_url = None;
+    let mut by_domain = None;
+    let mut by_cidr = None;
 
-    for (_section_name, sec) in sections.iter() {
+    for (name, sec) in sections {
         #[cfg(feature = "debug")]
         debug!(
             "  Checking section '{}' with URL patterns: {:?}",
-            _section_name, sec.url
+            name, sec.url
         );
 
-        for pat in &sec.url {
-            let p = pat.to_lowercase();
-            let matches = h == p || h.ends_with(&format!(".{}", p));
-
-            #[cfg(feature = "debug")]
-            debug!(
-                "    Pattern '{}' {} match host '{}'",
-                pat,
-                if matches { "DOES" } else { "does NOT" },
-                host
-            );
-
-            if matches {
+        if by_url.is_none() {
+            if let Some(pat) = sec.url.iter().find(|pat| host_matches(&h, pat)) {
                 #[cfg(feature = "debug")]
                 info!(
                     "Host '{}' matched rule '{}' (pattern: '{}')",
-                    host, _section_name, pat
+                    host, name, pat
+                );
+                by_url = Some((normalize_host(pat), name, sec));
+            }
+        }
+
+        if let (None, Some(site)) = (&by_domain, &site) {
+            if let Some(_pat) = sec.domain.iter().find(|d| normalize_host(d) == *site) {
+                #[cfg(feature = "debug")]
+                info!(
+                    "Host '{}' matched rule '{}' (domain: '{}')",
+                    host, name, _pat
+                );
+                by_domain = Some((name, sec));
+            }
+        }
+
+        if let (None, Some(ip)) = (&by_cidr, ip) {
+            let in_range = |c: &&String| parse_cidr(c).is_some_and(|net| net.contains(&ip));
+            if let Some(_cidr) = sec.cidr.iter().find(in_range) {
+                #[cfg(feature = "debug")]
+                info!(
+                    "Host '{}' matched rule '{}' (cidr: '{}')",
+                    host, name, _cidr
                 );
-                return Some(sec);
+                by_cidr = Some((name, sec));
             }
         }
     }
 
+    let found = match (by_url, by_domain) {
+        (Some((pat, ..)), Some(domain)) if site.as_ref().is_some_and(|s| s.len() > pat.len()) => {
+            Some(domain)
+        }
+        (Some((_, name, sec)), _) => Some((name, sec)),
+        (None, domain) => domain,
+    }
+    .or(by_cidr);
+
     #[cfg(feature = "debug")]
-    debug!("No host match found for '{}'", host);
-    None
+    if found.is_none() {
+        debug!("No host match found for '{}'", host);
+    }
+    found
+}
+
+/// Whether a section is ruled out for this link by `exclude_url` or `exclude_clients`.
+pub fn is_excluded(sec: &RuleSection, host: &str, client: Option<&str>) -> bool {
+    let host = normalize_host(host);
+    let excluded = (!host.is_empty() && sec.exclude_url.iter().any(|p| host_matches(&host, p)))
+        || client.is_some_and(|c| sec.exclude_clients.iter().any(|n| n.matches(c)));
+
+    #[cfg(feature = "debug")]
+    if excluded {
+        debug!("Section with browser '{}' excludes this link", sec.browser);
+    }
+    excluded
+}
+
+/// Keeps the sections that handle `scheme`.
+fn for_scheme<'a>(
+    sections: impl IntoIterator<Item = (&'a String, &'a RuleSection)>,
+    scheme: &str,
+) -> Vec<(&'a String, &'a RuleSection)> {
+    sections
+        .into_iter()
+        .filter(|(_, s)| s.accepts_scheme(scheme))
+        .collect()
+}
+
+/// Whether normalized `host` is `pattern` or one of its subdomains.
+pub(crate) fn host_matches(host: &str, pattern: &str) -> bool {
+    let p = normalize_host(pattern);
+    host == p || host.ends_with(&format!(".{}", p))
+}
+
+/// How a `clients` entry is compared with a client name, ignoring case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClientPattern<'a> {
+    /// `=code` matches `code` only.
+    Exact(&'a str),
+    /// `^code` matches clients starting with `code`.
+    Prefix(&'a str),
+    /// `/^code(-oss)?$/` matches clients the regex finds a match in.
+    Regex(&'a str),
+    /// `code` matches clients containing `code`, the default.
+    Substring(&'a str),
+}
+
+impl<'a> ClientPattern<'a> {
+    pub(crate) fn parse(pattern: &'a str) -> Self {
+        if let Some(exact) = pattern.strip_prefix('=') {
+            ClientPattern::Exact(exact)
+        } else if let Some(prefix) = pattern.strip_prefix('^') {
+            ClientPattern::Prefix(prefix)
+        } else if let Some(regex) = pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            ClientPattern::Regex(regex)
+        } else {
+            ClientPattern::Substring(pattern)
+        }
+    }
+
+    /// Compiles a `/regex/` pattern's source, ignoring case.
+    ///
+    /// The error is the compiler's reason alone, without the pattern it repeats.
+    pub(crate) fn regex(source: &str) -> Result<Regex, String> {
+        RegexBuilder::new(source)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| {
+                let reason = e.to_string();
+                let reason = reason.lines().last().unwrap_or_default().trim();
+                reason.strip_prefix("error: ").unwrap_or(reason).to_string()
+            })
+    }
+}
+
+/// A `clients` or `exclude_clients` entry, with its regex compiled when the
+/// config is loaded so matching never compiles it again.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ClientMatcher {
+    pattern: String,
+    regex: Option<Regex>,
+}
+
+impl ClientMatcher {
+    /// The entry as written in the config.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `client` matches this entry, ignoring case.
+    pub fn matches(&self, client: &str) -> bool {
+        let client = client.to_lowercase();
+        match ClientPattern::parse(&self.pattern) {
+            ClientPattern::Exact(p) => client == p.to_lowercase(),
+            ClientPattern::Prefix(p) => client.starts_with(&p.to_lowercase()),
+            ClientPattern::Regex(_) => self.regex.as_ref().is_some_and(|re| re.is_match(&client)),
+            ClientPattern::Substring(p) => client.contains(&p.to_lowercase()),
+        }
+    }
+}
+
+impl FromStr for ClientMatcher {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let regex = match ClientPattern::parse(pattern) {
+            ClientPattern::Regex(source) => Some(
+                ClientPattern::regex(source)
+                    .map_err(|e| format!("'{}' is not a valid regex: {}", pattern, e))?,
+            ),
+            _ => None,
+        };
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+}
+
+impl TryFrom<String> for ClientMatcher {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        pattern.parse()
+    }
+}
+
+impl fmt::Debug for ClientMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.pattern, f)
+    }
+}
+
+impl fmt::Display for ClientMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl PartialEq<&str> for ClientMatcher {
+    fn eq(&self, other: &&str) -> bool {
+        self.pattern == *other
+    }
+}
+
+/// Finds a section that lists `scheme` in `schemes` and has no client or host rules.
+pub fn match_scheme<'a>(
+    scheme: &str,
+    sections: impl IntoIterator<Item = (&'a String, &'a RuleSection)>,
+) -> Option<(&'a String, &'a RuleSection)> {
+    sections
+        .into_iter()
+        .filter(|(_, s)| {
+            s.clients.is_empty() && s.url.is_empty() && s.domain.is_empty() && s.cidr.is_empty()
+        })
+        .find(|(_, s)| s.schemes.iter().any(|x| x.eq_ignore_ascii_case(scheme)))
+        .inspect(|(_name, _)| {
+            #[cfg(feature = "debug")]
+            info!("Scheme '{}' matched rule '{}'", scheme, _name);
+        })
 }
 
 #[cfg(test)]
@@ -148,6 +576,275 @@ mod tests {
         assert_eq!(parse_url_host("GitHub.COM").unwrap(), "github.com");
     }
 
+    #[test]
+    fn test_parse_url_host_other_schemes() {
+        assert_eq!(parse_url_host("localhost:3000/x").unwrap(), "localhost");
+        assert_eq!(
+            parse_url_host("mailto:Bob@Company.com?subject=hi").unwrap(),
+            "company.com"
+        );
+        assert_eq!(parse_url_host("mailto:?subject=hi").unwrap(), "");
+        assert_eq!(parse_url_host("tel:+15551234").unwrap(), "");
+        assert_eq!(parse_url_host("tel:5551234").unwrap(), "");
+        assert_eq!(parse_url_host("file:///home/me/index.html").unwrap(), "");
+        assert_eq!(
+            parse_url_host("slack://channel?team=T1&id=C1").unwrap(),
+            "channel"
+        );
+        assert_eq!(parse_url_host("spotify:track:4uLU6hMC").unwrap(), "");
+
+        assert_eq!(parse_url_scheme("github.com").unwrap(), "http");
+        assert_eq!(parse_url_scheme("localhost:3000").unwrap(), "http");
+        assert_eq!(
+            parse_url_scheme("MAILTO:bob@example.com").unwrap(),
+            "mailto"
+        );
+        assert_eq!(
+            parse_url_scheme("zoommtg://zoom.us/join").unwrap(),
+            "zoommtg"
+        );
+        assert_eq!(
+            parse_url_scheme("spotify:track:4uLU6hMC").unwrap(),
+            "spotify"
+        );
+    }
+
+    #[test]
+    fn test_match_by_scheme() {
+        let mut sections = HashMap::new();
+        sections.insert(
+            "work-mail".to_string(),
+            RuleSection {
+                browser: "thunderbird".to_string(),
+                clients: vec!["slack".parse().unwrap()],
+                url: vec!["company.com".to_string()],
+                schemes: vec!["mailto".to_string()],
+                ..Default::default()
+            },
+        );
+        sections.insert(
+            "mail".to_string(),
+            RuleSection {
+                browser: "geary".to_string(),
+                schemes: vec!["mailto".to_string()],
+                ..Default::default()
+            },
+        );
+        sections.insert(
+            "work".to_string(),
+            RuleSection {
+                browser: "chrome".to_string(),
+                clients: vec!["slack".parse().unwrap()],
+                url: vec!["company.com".to_string()],
+                ..Default::default()
+            },
+        );
+
+        fn name<'a>(s: Option<(&'a String, &RuleSection)>) -> Option<&'a str> {
+            s.map(|(name, _)| name.as_str())
+        }
+        assert_eq!(
+            name(match_client_for("Slack", "mailto", &sections)),
+            Some("work-mail")
+        );
+        assert_eq!(
+            match_client("Slack", &sections).map(|s| s.browser.as_str()),
+            Some("chrome")
+        );
+        assert_eq!(
+            name(match_host_for("company.com", "mailto", &sections)),
+            Some("work-mail")
+        );
+        assert_eq!(
+            match_host("company.com", &sections).map(|s| s.browser.as_str()),
+            Some("chrome")
+        );
+        assert_eq!(name(match_scheme("mailto", &sections)), Some("mail"));
+        assert_eq!(name(match_scheme("https", &sections)), None);
+        assert_eq!(name(match_client_for("slack", "tel", &sections)), None);
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("München.de"), "xn--mnchen-3ya.de");
+        assert_eq!(normalize_host("xn--mnchen-3ya.de"), "xn--mnchen-3ya.de");
+        assert_eq!(normalize_host("Example.COM."), "example.com");
+        assert_eq!(normalize_host("[::1]"), "[::1]");
+        assert_eq!(normalize_host("::1"), "[::1]");
+        assert_eq!(normalize_host("FD00::0:1"), "[fd00::1]");
+        assert_eq!(normalize_host("127.0.0.1"), "127.0.0.1");
+        assert_eq!(
+            parse_url_host("https://example.com./a").unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            parse_url_host("https://münchen.de/").unwrap(),
+            "xn--mnchen-3ya.de"
+        );
+    }
+
+    #[test]
+    fn test_match_host_normalized() {
+        let mut sections = HashMap::new();
+        sections.insert(
+            "idn".to_string(),
+            RuleSection {
+                browser: "firefox".to_string(),
+                url: vec!["münchen.de".to_string(), "example.org.".to_string()],
+                ..Default::default()
+            },
+        );
+
+        assert!(match_host("xn--mnchen-3ya.de", &sections).is_some());
+        assert!(match_host("www.xn--mnchen-3ya.de", &sections).is_some());
+        assert!(match_host("MÜNCHEN.DE.", &sections).is_some());
+        assert!(match_host("example.org", &sections).is_some());
+        assert!(match_host("example.org.", &sections).is_some());
+        assert!(match_host("example.net", &sections).is_none());
+    }
+
+    #[test]
+    fn test_match_registrable_domain() {
+        let mut sections = HashMap::new();
+        sections.insert(
+            "alice".to_string(),
+            RuleSection {
+                browser: "firefox".to_string(),
+                domain: vec!["alice.github.io".to_string(), "example.co.uk".to_string()],
+                ..Default::default()
+            },
+        );
+        sections.insert(
+            "bob".to_string(),
+            RuleSection {
+                browser: "chrome".to_string(),
+                domain: vec!["bob.github.io".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let browser = |host| match_host(host, &sections).map(|s| s.browser.clone());
+        assert_eq!(browser("alice.github.io").as_deref(), Some("firefox"));
+        assert_eq!(browser("docs.alice.github.io").as_deref(), Some("firefox"));
+        assert_eq!(browser("bob.github.io").as_deref(), Some("chrome"));
+        assert_eq!(browser("www.example.co.uk").as_deref(), Some("firefox"));
+        assert_eq!(browser("carol.github.io"), None);
+        assert_eq!(browser("github.io"), None);
+        assert_eq!(browser("co.uk"), None);
+    }
+
+    #[test]
+    fn test_domain_wins_over_broader_url() {
+        let rule = |browser: &str, url: &[&str], domain: &[&str], cidr: &[&str]| RuleSection {
+            browser: browser.to_string(),
+            url: url.iter().map(|s| s.to_string()).collect(),
+            domain: domain.iter().map(|s| s.to_string()).collect(),
+            cidr: cidr.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let sections = HashMap::from([
+            ("pages".to_string(), rule("brave", &["github.io"], &[], &[])),
+            (
+                "alice".to_string(),
+                rule("firefox", &[], &["alice.github.io"], &[]),
+            ),
+            (
+                "lan".to_string(),
+                rule("epiphany", &[], &[], &["10.0.0.0/8"]),
+            ),
+            ("nas".to_string(), rule("falkon", &["10.0.0.5"], &[], &[])),
+        ]);
+
+        let browser = |host| match_host(host, &sections).map(|s| s.browser.clone());
+        assert_eq!(browser("alice.github.io").as_deref(), Some("firefox"));
+        assert_eq!(browser("blog.alice.github.io").as_deref(), Some("firefox"));
+        assert_eq!(browser("carol.github.io").as_deref(), Some("brave"));
+        assert_eq!(browser("10.0.0.5").as_deref(), Some("falkon"));
+        assert_eq!(browser("10.0.0.6").as_deref(), Some("epiphany"));
+
+        // A `url` pattern naming a host inside the domain is the more specific rule.
+        let sections = HashMap::from([
+            (
+                "alice".to_string(),
+                rule("firefox", &[], &["alice.github.io"], &[]),
+            ),
+            (
+                "docs".to_string(),
+                rule("chromium", &["docs.alice.github.io"], &[], &[]),
+            ),
+        ]);
+        let browser = |host| match_host(host, &sections).map(|s| s.browser.clone());
+        assert_eq!(browser("docs.alice.github.io").as_deref(), Some("chromium"));
+        assert_eq!(browser("blog.alice.github.io").as_deref(), Some("firefox"));
+    }
+
+    #[test]
+    fn test_match_cidr() {
+        let mut sections = HashMap::new();
+        sections.insert(
+            "internal".to_string(),
+            RuleSection {
+                browser: "chromium".to_string(),
+                cidr: vec![
+                    "10.0.0.0/8".to_string(),
+                    "192.168.1.20".to_string(),
+                    "fd00::/8".to_string(),
+                    "::1/128".to_string(),
+                ],
+                ..Default::default()
+            },
+        );
+        sections.insert(
+            "local".to_string(),
+            RuleSection {
+                browser: "firefox".to_string(),
+                url: vec!["127.0.0.1".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let browser = |u| {
+            let host = parse_url_host(u).unwrap();
+            match_host(&host, &sections).map(|s| s.browser.clone())
+        };
+        assert_eq!(
+            browser("http://10.20.30.40:8080/").as_deref(),
+            Some("chromium")
+        );
+        assert_eq!(browser("http://192.168.1.20").as_deref(), Some("chromium"));
+        assert_eq!(browser("http://192.168.1.21"), None);
+        assert_eq!(
+            browser("http://[fd12:3456::1]/x").as_deref(),
+            Some("chromium")
+        );
+        assert_eq!(browser("http://127.0.0.1:3000").as_deref(), Some("firefox"));
+        assert_eq!(browser("http://11.0.0.1"), None);
+        assert_eq!(browser("https://10.example.com"), None);
+        assert_eq!(browser("http://[::1]:8080/").as_deref(), Some("chromium"));
+    }
+
+    #[test]
+    fn test_client_match_modes() {
+        let matches =
+            |client: &str, pattern: &str| pattern.parse::<ClientMatcher>().unwrap().matches(client);
+        assert!(matches("VSCode", "code"));
+        assert!(matches("code", "=code"));
+        assert!(matches("Code", "=CODE"));
+        assert!(!matches("vscode", "=code"));
+        assert!(matches("code-oss", "^code"));
+        assert!(!matches("barcode-scanner", "^code"));
+        assert!(matches("code-oss", "/^code(-oss)?$/"));
+        assert!(matches("Code", "/^code(-oss)?$/"));
+        assert!(!matches("codeium", "/^code(-oss)?$/"));
+        assert!(matches("a/b", "/"));
+
+        let err = "/(/".parse::<ClientMatcher>().unwrap_err();
+        assert!(err.starts_with("'/(/' is not a valid regex: "), "{}", err);
+
+        assert_eq!(ClientPattern::parse("/"), ClientPattern::Substring("/"));
+        assert_eq!(ClientPattern::parse("//"), ClientPattern::Regex(""));
+    }
+
     #[test]
     fn test_parse_url_host_invalid() {
         assert!(parse_url_host("not a url").is_err());
@@ -161,8 +858,9 @@ mod tests {
             "work".to_string(),
             RuleSection {
                 browser: "chrome".to_string(),
-                clients: vec!["slack".to_string()],
+                clients: vec!["slack".parse().unwrap()],
                 url: vec![],
+                ..Default::default()
             },
         );
 
@@ -178,8 +876,9 @@ mod tests {
             "work".to_string(),
             RuleSection {
                 browser: "chrome".to_string(),
-                clients: vec!["slack".to_string()],
+                clients: vec!["slack".parse().unwrap()],
                 url: vec![],
+                ..Default::default()
             },
         );
 
@@ -195,8 +894,9 @@ mod tests {
             "work".to_string(),
             RuleSection {
                 browser: "chrome".to_string(),
-                clients: vec!["Slack".to_string()],
+                clients: vec!["Slack".parse().unwrap()],
                 url: vec![],
+                ..Default::default()
             },
         );
 
@@ -212,8 +912,9 @@ mod tests {
             "work".to_string(),
             RuleSection {
                 browser: "chrome".to_string(),
-                clients: vec!["slack".to_string()],
+                clients: vec!["slack".parse().unwrap()],
                 url: vec![],
+                ..Default::default()
             },
         );
 
@@ -230,6 +931,7 @@ mod tests {
                 browser: "firefox".to_string(),
                 clients: vec![],
                 url: vec!["github.com".to_string()],
+                ..Default::default()
             },
         );
 
@@ -247,6 +949,7 @@ mod tests {
                 browser: "firefox".to_string(),
                 clients: vec![],
                 url: vec!["github.com".to_string()],
+                ..Default::default()
             },
         );
 
@@ -268,6 +971,7 @@ mod tests {
                 browser: "firefox".to_string(),
                 clients: vec![],
                 url: vec!["GitHub.COM".to_string()],
+                ..Default::default()
             },
         );
 
@@ -285,6 +989,7 @@ mod tests {
                 browser: "firefox".to_string(),
                 clients: vec![],
                 url: vec!["github.com".to_string()],
+                ..Default::default()
             },
         );
 
@@ -301,6 +1006,7 @@ mod tests {
                 browser: "firefox".to_string(),
                 clients: vec![],
                 url: vec!["github.com".to_string()],
+                ..Default::default()
             },
         );
 
@@ -315,16 +1021,18 @@ mod tests {
             "work".to_string(),
             RuleSection {
                 browser: "chrome".to_string(),
-                clients: vec!["slack".to_string()],
+                clients: vec!["slack".parse().unwrap()],
                 url: vec![],
+                ..Default::default()
             },
         );
         sections.insert(
             "personal".to_string(),
             RuleSection {
                 browser: "firefox".to_string(),
-                clients: vec!["discord".to_string()],
+                clients: vec!["discord".parse().unwrap()],
                 url: vec![],
+                ..Default::default()
             },
         );
 
@@ -336,4 +1044,78 @@ mod tests {
             "firefox"
         );
     }
+
+    fn privacy_config(extra: &str) -> Config {
+        toml::from_str(&format!(
+            "[privacy]\nstrip_params = true\n{}\n[default]\nbrowser = \"firefox\"\n",
+            extra
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_clean_url_strips_defaults() {
+        let cfg = privacy_config("");
+        assert_eq!(
+            clean_url(
+                &cfg,
+                "https://example.com/a?utm_source=x&id=1&fbclid=abc&UTM_Medium=y#frag"
+            ),
+            "https://example.com/a?id=1#frag"
+        );
+        assert_eq!(
+            clean_url(&cfg, "https://example.com/?gclid=1&si=2"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            clean_url(&cfg, "https://example.com/?q=a%20b&x=%26"),
+            "https://example.com/?q=a%20b&x=%26"
+        );
+    }
+
+    #[test]
+    fn test_clean_url_unwraps_then_strips() {
+        let cfg = privacy_config("");
+        assert_eq!(
+            clean_url(
+                &cfg,
+                "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F%3Futm_source%3Dx%26id%3D1&sa=D"
+            ),
+            "https://example.com/?id=1"
+        );
+
+        let cfg = privacy_config("[unwrap]\nenabled = false");
+        let wrapped = "https://www.google.com/url?q=https%3A%2F%2Fexample.com%2F";
+        assert_eq!(clean_url(&cfg, wrapped), wrapped);
+    }
+
+    #[test]
+    fn test_clean_url_disabled_by_default() {
+        let cfg: Config = toml::from_str("[default]\nbrowser = \"firefox\"").unwrap();
+        let url = "https://example.com/?utm_source=x";
+        assert_eq!(clean_url(&cfg, url), url);
+    }
+
+    #[test]
+    fn test_clean_url_user_params_and_host_overrides() {
+        let cfg = privacy_config(
+            r#"params = ["ref_*"]
+
+[privacy.hosts."youtube.com"]
+keep = ["si"]
+strip = ["feature"]
+"#,
+        );
+        assert_eq!(
+            clean_url(&cfg, "https://example.com/?ref_src=tw&feature=x"),
+            "https://example.com/?feature=x"
+        );
+        assert_eq!(
+            clean_url(
+                &cfg,
+                "https://www.youtube.com/watch?v=1&si=abc&feature=share&utm_source=x"
+            ),
+            "https://www.youtube.com/watch?v=1&si=abc"
+        );
+    }
 }
@@ -0,0 +1,251 @@
+//! Interactive browser choice for `action = "picker"`.
+//!
+//! This module lists the configured browsers in a dmenu-compatible menu
+//! (fuzzel, wofi, rofi, tofi, bemenu, dmenu) and launches the selected one,
+//! optionally saving the choice as a host rule.
+
+use crate::check::browser_exists;
+use crate::config::{config_path, Config, PickerSection};
+use crate::launcher::{launch_browser_with_token, ActivationToken};
+use crate::matcher::parse_url_host;
+use crate::remember::remember_host;
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "debug")]
+use log::{debug, info};
+
+/// Menus tried in order when `[picker] command` is unset, with their dmenu mode flags.
+const PICKERS: &[(&str, &str)] = &[
+    ("fuzzel", "fuzzel --dmenu"),
+    ("wofi", "wofi --dmenu"),
+    ("rofi", "rofi -dmenu"),
+    ("tofi", "tofi"),
+    ("bemenu", "bemenu"),
+    ("dmenu", "dmenu"),
+];
+
+const ONCE: &str = "Just this once";
+
+/// The configured picker command, or the first known menu installed.
+pub fn picker_command(picker: &PickerSection) -> Option<String> {
+    if let Some(command) = &picker.command {
+        return Some(command.clone());
+    }
+    PICKERS
+        .iter()
+        .find(|(program, _)| browser_exists(program))
+        .map(|(_, command)| command.to_string())
+}
+
+/// Browsers to offer: `preferred` first, then the default, then every rule's browser.
+pub fn browsers<'a>(cfg: &'a Config, preferred: &'a str) -> Vec<&'a str> {
+    let mut sections: Vec<_> = cfg.sections.iter().collect();
+    sections.sort_by_key(|(name, _)| name.as_str());
+
+    let mut browsers: Vec<&str> = Vec::new();
+    let candidates = [preferred, cfg.default.browser.as_str()]
+        .into_iter()
+        .chain(sections.into_iter().map(|(_, sec)| sec.browser.as_str()));
+    for browser in candidates {
+        if !browser.trim().is_empty() && !browsers.contains(&browser) {
+            browsers.push(browser);
+        }
+    }
+    browsers
+}
+
+/// Runs `command` through the shell with one entry per line on stdin.
+///
+/// Returns the selected line, or `None` when the menu was dismissed.
+pub fn pick(command: &str, entries: &[&str]) -> Result<Option<String>> {
+    #[cfg(feature = "debug")]
+    debug!("Running picker '{}' with {:?}", command, entries);
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run picker '{}'", command))?;
+
+    let mut input = entries.join("\n");
+    input.push('\n');
+    if let Some(mut stdin) = child.stdin.take() {
+        // A menu may exit before reading everything; its output still counts.
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run picker '{}'", command))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let choice = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((!choice.is_empty()).then_some(choice))
+}
+
+/// Asks whether the choice should stick for `url`'s host, returning the host if so.
+fn ask_remember(command: &str, url: &str) -> Result<Option<String>> {
+    let Ok(host) = parse_url_host(url) else {
+        return Ok(None);
+    };
+    let always = format!("Always for {}", host);
+    let choice = pick(command, &[ONCE, &always])?;
+    Ok((choice.as_deref() == Some(always.as_str())).then_some(host))
+}
+
+/// Asks which browser should open `url` and launches it.
+///
+/// `routed` is the link before the route's rewrites; a remembered choice uses
+/// its host, since rules match links before they are rewritten. `preferred` is
+/// the browser the matched rule names; it is listed first.
+pub fn run(
+    cfg: &Config,
+    url: &str,
+    routed: &str,
+    preferred: &str,
+    token: Option<&ActivationToken>,
+) -> Result<()> {
+    let command = picker_command(&cfg.picker)
+        .context("No picker found; install fuzzel, wofi, rofi, tofi, bemenu or dmenu, or set 'command' in [picker]")?;
+    let browsers = browsers(cfg, preferred);
+
+    let Some(choice) = pick(&command, &browsers)? else {
+        #[cfg(feature = "debug")]
+        info!("Picker dismissed, not opening '{}'", url);
+        return Ok(());
+    };
+    if !browsers.contains(&choice.as_str()) {
+        bail!("'{}' is not one of the configured browsers", choice);
+    }
+
+    #[cfg(feature = "debug")]
+    info!("Picked browser: '{}'", choice);
+
+    let remember = if cfg.picker.remember {
+        ask_remember(&command, routed)?
+    } else {
+        None
+    };
+
+    launch_browser_with_token(&choice, url, token)?;
+
+    if let Some(host) = remember {
+        remember_host(&config_path(), &host, &choice)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+action = "picker"
+
+[work]
+browser = "chromium"
+url = ["company.com"]
+
+[social]
+browser = "brave"
+url = ["reddit.com"]
+
+[docs]
+browser = "chromium"
+url = ["docs.rs"]
+"#,
+        )
+        .unwrap()
+    }
+
+    /// A picker that records its input and answers with `reply`.
+    fn stub(dir: &Path, reply: &str) -> String {
+        let script = dir.join("picker.sh");
+        fs::write(
+            &script,
+            format!(
+                "cat > {}\nprintf '%s\\n' '{}'\n",
+                dir.join("input").display(),
+                reply
+            ),
+        )
+        .unwrap();
+        format!("sh {}", script.display())
+    }
+
+    #[test]
+    fn test_browsers_order() {
+        let cfg = config();
+        assert_eq!(
+            browsers(&cfg, "chromium"),
+            vec!["chromium", "firefox", "brave"]
+        );
+        assert_eq!(
+            browsers(&cfg, "firefox"),
+            vec!["firefox", "chromium", "brave"]
+        );
+    }
+
+    #[test]
+    fn test_pick_reads_choice() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = stub(dir.path(), "brave");
+
+        let choice = pick(&command, &["firefox", "brave"]).unwrap();
+        assert_eq!(choice.as_deref(), Some("brave"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("input")).unwrap(),
+            "firefox\nbrave\n"
+        );
+    }
+
+    #[test]
+    fn test_pick_dismissed() {
+        assert_eq!(pick("cat > /dev/null; exit 1", &["firefox"]).unwrap(), None);
+        assert_eq!(pick("cat > /dev/null", &["firefox"]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_picker_command() {
+        let picker = PickerSection {
+            command: Some("fuzzel --dmenu --prompt 'Open with '".into()),
+            remember: false,
+        };
+        assert_eq!(
+            picker_command(&picker).as_deref(),
+            Some("fuzzel --dmenu --prompt 'Open with '")
+        );
+    }
+
+    #[test]
+    fn test_run_rejects_unknown_choice() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = config();
+        cfg.picker.command = Some(stub(dir.path(), "rm -rf"));
+
+        let err = run(
+            &cfg,
+            "https://example.com",
+            "https://example.com",
+            "firefox",
+            None,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("not one of the configured browsers"));
+    }
+}
@@ -0,0 +1,382 @@
+//! Writing host rules back to the config file.
+//!
+//! This module edits `config.toml` with a format-preserving TOML editor so the
+//! user's comments, ordering and list layout survive a remembered choice.
+
+use crate::config::{config_path, RESERVED_SECTIONS};
+use crate::matcher::{host_matches, normalize_host, parse_url_host};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use toml_edit::{Array, DocumentMut, Item, Table, TableLike, Value};
+
+#[cfg(feature = "debug")]
+use log::info;
+
+/// Routes `host` to `target` from now on, returning the section it was added to.
+///
+/// `target` is a rule section's name or a browser. A browser's host goes into
+/// the first section that already opens links in it, or a new section named
+/// after it. A section that would not open the host in its browser, such as a
+/// picker section, is passed over for a new one. The host is dropped from
+/// every other section.
+pub fn remember_host(path: &Path, host: &str, target: &str) -> Result<String> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config at {}", path.display()))?;
+    let mut doc: DocumentMut = source
+        .parse()
+        .with_context(|| format!("Invalid TOML in {}", path.display()))?;
+
+    let section = add_host(&mut doc, host, target)?;
+    fs::write(path, doc.to_string())
+        .with_context(|| format!("Failed to write config at {}", path.display()))?;
+
+    #[cfg(feature = "debug")]
+    info!("Remembered '{}' for '{}' in [{}]", host, target, section);
+    Ok(section)
+}
+
+/// Runs `hyprchoosy remember <host> <target>`; `host` may also be a full URL.
+///
+/// Returns the normalized host and the section it was added to.
+pub fn run(host: &str, target: &str) -> Result<(String, String)> {
+    if target.trim().is_empty() {
+        bail!("No browser or section given");
+    }
+    let host = parse_url_host(host)?;
+    let section = remember_host(&config_path(), &host, target)?;
+    Ok((host, section))
+}
+
+/// Rule sections of `doc`, in file order.
+fn rule_sections(doc: &DocumentMut) -> impl Iterator<Item = (&str, &dyn TableLike)> {
+    doc.as_table()
+        .iter()
+        .filter(|(name, _)| !RESERVED_SECTIONS.contains(name))
+        .filter_map(|(name, item)| Some((name, item.as_table_like()?)))
+}
+
+/// Whether links to `host` added to the section would open in its browser.
+///
+/// A section that shows the picker or runs another action, handles only other
+/// schemes, or excludes the host or some clients would not honour the choice.
+fn opens_in_browser(table: &dyn TableLike, host: &str) -> bool {
+    let strings = |key| {
+        table
+            .get(key)
+            .and_then(Item::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+    };
+    let launches = table
+        .get("action")
+        .is_none_or(|action| action.as_str() == Some("launch"));
+    let web = strings("schemes").next().is_none()
+        || strings("schemes").any(|s| s.eq_ignore_ascii_case("https"));
+    let excluded = strings("exclude_url").any(|p| host_matches(host, p))
+        || strings("exclude_clients").next().is_some();
+    launches && web && !excluded
+}
+
+/// Where a remembered host goes.
+enum Destination {
+    /// An existing section.
+    Section(String),
+    /// A new section for this browser.
+    NewSection(String),
+}
+
+/// Picks `target` itself, or the first section using `target` as its browser,
+/// unless it wouldn't open `host` in its browser.
+fn find_section(doc: &DocumentMut, host: &str, target: &str) -> Result<Destination> {
+    if let Some((_, table)) = rule_sections(doc).find(|(name, _)| *name == target) {
+        if opens_in_browser(table, host) {
+            return Ok(Destination::Section(target.to_string()));
+        }
+        return match table.get("browser").and_then(Item::as_str) {
+            Some(browser) if !browser.trim().is_empty() => {
+                Ok(Destination::NewSection(browser.to_string()))
+            }
+            _ => bail!("[{}] has no browser to open {} in", target, host),
+        };
+    }
+    Ok(rule_sections(doc)
+        .find(|(_, table)| {
+            table.get("browser").and_then(Item::as_str) == Some(target)
+                && opens_in_browser(*table, host)
+        })
+        .map_or_else(
+            || Destination::NewSection(target.to_string()),
+            |(name, _)| Destination::Section(name.to_string()),
+        ))
+}
+
+fn add_host(doc: &mut DocumentMut, host: &str, target: &str) -> Result<String> {
+    let host = normalize_host(host);
+
+    let section = match find_section(doc, &host, target)? {
+        Destination::Section(section) => section,
+        Destination::NewSection(browser) => {
+            let section = unique_section_name(doc, target);
+            let mut table = Table::new();
+            table.insert("browser", browser.as_str().into());
+            doc.insert(&section, Item::Table(table));
+            section
+        }
+    };
+
+    for (name, item) in doc.as_table_mut().iter_mut() {
+        if name.get() == section || RESERVED_SECTIONS.contains(&name.get()) {
+            continue;
+        }
+        if let Some(urls) = item.get_mut("url").and_then(Item::as_array_mut) {
+            urls.retain(|v| v.as_str().map(normalize_host).as_deref() != Some(&host));
+        }
+    }
+
+    let table = doc[&section]
+        .as_table_like_mut()
+        .expect("section was just found or created");
+    let urls = table
+        .entry("url")
+        .or_insert_with(|| Item::Value(Value::Array(Array::new())));
+    if let Some(urls) = urls.as_array_mut() {
+        append(urls, &host);
+    }
+    Ok(section)
+}
+
+/// Pushes `host` unless present, matching the layout of a multi-line list.
+fn append(urls: &mut Array, host: &str) {
+    if urls
+        .iter()
+        .any(|v| v.as_str().is_some_and(|u| normalize_host(u) == host))
+    {
+        return;
+    }
+
+    let prefix = urls
+        .iter()
+        .last()
+        .and_then(|v| v.decor().prefix())
+        .and_then(|p| p.as_str())
+        .filter(|p| p.contains('\n'))
+        .map(String::from);
+
+    match prefix {
+        Some(prefix) => {
+            // A comment after the last entry stays with that entry.
+            let trailing = urls.trailing().as_str().unwrap_or_default().to_string();
+            let (comment, rest) = trailing.split_at(trailing.rfind('\n').unwrap_or(0));
+            let mut value = Value::from(host);
+            value
+                .decor_mut()
+                .set_prefix(format!("{}{}", comment, prefix));
+            urls.set_trailing(rest);
+            urls.push_formatted(value);
+        }
+        None => urls.push(host),
+    }
+}
+
+/// A section name derived from the browser command that isn't taken yet.
+fn unique_section_name(doc: &DocumentMut, browser: &str) -> String {
+    let program = browser.split_whitespace().next().unwrap_or_default();
+    let base: String = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    let base = if base.is_empty() {
+        "remembered".to_string()
+    } else {
+        base
+    };
+
+    let mut name = base.clone();
+    let mut n = 2;
+    while doc.contains_key(&name) || RESERVED_SECTIONS.contains(&name.as_str()) {
+        name = format!("{}-{}", base, n);
+        n += 1;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remember(source: &str, host: &str, browser: &str) -> (String, String) {
+        let mut doc: DocumentMut = source.parse().unwrap();
+        let section = add_host(&mut doc, host, browser).unwrap();
+        (section, doc.to_string())
+    }
+
+    #[test]
+    fn test_appends_to_section_using_browser() {
+        let source = r#"# My rules
+[default]
+browser = "firefox"
+
+# Work stuff
+[work]
+browser = "chromium" # the work profile
+url = ["company.com"]
+"#;
+        let (section, result) = remember(source, "Linear.app", "chromium");
+        assert_eq!(section, "work");
+        assert_eq!(
+            result,
+            r#"# My rules
+[default]
+browser = "firefox"
+
+# Work stuff
+[work]
+browser = "chromium" # the work profile
+url = ["company.com", "linear.app"]
+"#
+        );
+    }
+
+    #[test]
+    fn test_keeps_multiline_lists_and_skips_duplicates() {
+        let source = r#"[work]
+browser = "chromium"
+url = [
+    "company.com",
+    "jira.com", # tickets
+]
+"#;
+        let (_, result) = remember(source, "linear.app", "chromium");
+        assert_eq!(
+            result,
+            r#"[work]
+browser = "chromium"
+url = [
+    "company.com",
+    "jira.com", # tickets
+    "linear.app",
+]
+"#
+        );
+
+        let (_, again) = remember(&result, "LINEAR.app", "chromium");
+        assert_eq!(again, result);
+    }
+
+    #[test]
+    fn test_creates_section_and_moves_host() {
+        let source = r#"[default]
+browser = "firefox"
+
+[brave]
+browser = "chromium"
+url = ["reddit.com", "twitter.com"]
+"#;
+        let (section, result) = remember(source, "reddit.com", "/usr/bin/brave --incognito");
+        assert_eq!(section, "brave-2");
+
+        let config: crate::config::Config = toml::from_str(&result).unwrap();
+        assert_eq!(config.sections["brave"].url, vec!["twitter.com"]);
+        let created = &config.sections["brave-2"];
+        assert_eq!(created.browser, "/usr/bin/brave --incognito");
+        assert_eq!(created.url, vec!["reddit.com"]);
+    }
+
+    #[test]
+    fn test_skips_picker_section() {
+        let source = r#"[default]
+browser = "firefox"
+
+[ask]
+browser = "chromium"
+action = "picker"
+url = ["linear.app"]
+
+[mail]
+browser = "chromium"
+schemes = ["mailto"]
+"#;
+        let (section, result) = remember(source, "Linear.App.", "chromium");
+        assert_eq!(section, "chromium");
+
+        let config: crate::config::Config = toml::from_str(&result).unwrap();
+        assert!(config.sections["ask"].url.is_empty());
+        assert!(config.sections["mail"].url.is_empty());
+        assert_eq!(config.sections["chromium"].url, vec!["linear.app"]);
+    }
+
+    #[test]
+    fn test_skips_section_excluding_host() {
+        let source = r#"[google]
+browser = "chromium"
+url = ["google.com"]
+exclude_url = ["mail.google.com"]
+
+[chat]
+browser = "chromium"
+url = ["slack.com"]
+exclude_clients = ["kitty"]
+"#;
+        let (section, result) = remember(source, "mail.google.com", "google");
+        assert_eq!(section, "google-2");
+        let config: crate::config::Config = toml::from_str(&result).unwrap();
+        assert_eq!(config.sections["google-2"].browser, "chromium");
+        assert_eq!(config.sections["google-2"].url, vec!["mail.google.com"]);
+
+        let (section, _) = remember(source, "docs.google.com", "google");
+        assert_eq!(section, "google");
+        let (section, _) = remember(source, "mail.google.com", "chromium");
+        assert_eq!(section, "chromium");
+    }
+
+    #[test]
+    fn test_section_without_browser() {
+        let mut doc: DocumentMut = "[clip]\naction = \"copy\"\n".parse().unwrap();
+        let err = add_host(&mut doc, "example.com", "clip").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[clip] has no browser to open example.com in"
+        );
+    }
+
+    #[test]
+    fn test_section_name_as_target() {
+        let source = r#"[personal]
+browser = "firefox"
+
+[work]
+browser = "firefox -P work"
+url = ["company.com"]
+
+[firefox]
+browser = "chromium"
+"#;
+        let (section, result) = remember(source, "linear.app", "work");
+        assert_eq!(section, "work");
+        assert!(result.contains(r#"url = ["company.com", "linear.app"]"#));
+
+        // A section name wins over a browser of the same name.
+        let (section, _) = remember(source, "linear.app", "firefox");
+        assert_eq!(section, "firefox");
+    }
+
+    #[test]
+    fn test_remember_host_writes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "[default]\nbrowser = \"firefox\"\n").unwrap();
+
+        let section = remember_host(&path, "example.com", "firefox").unwrap();
+        assert_eq!(section, "firefox");
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            written,
+            "[default]\nbrowser = \"firefox\"\n\n[firefox]\nbrowser = \"firefox\"\nurl = [\"example.com\"]\n"
+        );
+    }
+}
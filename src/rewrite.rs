@@ -0,0 +1,240 @@
+//! URL rewriting between routing and launching.
+//!
+//! This module applies `rewrite` rules, such as `twitter.com` to `xcancel.com`
+//! or `zoom.us/j/{id}` to a `zoommtg://` link, to the URL a route opens.
+
+use crate::config::{Config, RewriteRule};
+use crate::matcher::{normalize_host, parse_url};
+use crate::router::Route;
+use crate::template::expand;
+use url::Url;
+
+#[cfg(feature = "debug")]
+use log::info;
+
+/// Applies the route's section rewrites, then the global ones, to `url`.
+///
+/// Every matching rule is applied in turn, so rules can build on each other.
+pub fn apply(cfg: &Config, route: &Route, url: &str) -> String {
+    route
+        .rewrite
+        .iter()
+        .chain(&cfg.rewrite)
+        .fold(url.to_string(), |url, rule| match rewrite(rule, &url) {
+            Some(rewritten) => {
+                #[cfg(feature = "debug")]
+                info!(
+                    "Rewrote '{}' to '{}' ({} -> {})",
+                    url, rewritten, rule.from, rule.to
+                );
+                rewritten
+            }
+            None => url,
+        })
+}
+
+/// Rewrites `url` with `rule`, or returns `None` if `from` doesn't match.
+pub fn rewrite(rule: &RewriteRule, url: &str) -> Option<String> {
+    let parsed = parse_url(url).ok()?;
+    let (captures, remainder) = match_from(&rule.from, &parsed)?;
+
+    let mut to = rule.to.clone();
+    for (name, value) in &captures {
+        to = to.replace(&format!("{{{}}}", name), value);
+    }
+    let to = expand(&to, url);
+
+    let (scheme, rest) = match to.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => (parsed.scheme(), to.as_str()),
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, parsed.query()),
+    };
+    let (host, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+
+    let mut out = format!("{}://{}", scheme, host);
+    // The port belongs to the original server, so it only survives when `to`
+    // keeps that host, and a web port never carries over to another scheme.
+    let web = |s: &str| s.eq_ignore_ascii_case("http") || s.eq_ignore_ascii_case("https");
+    let same_server = parsed.host_str().map(normalize_host) == Some(normalize_host(host))
+        && (scheme.eq_ignore_ascii_case(parsed.scheme()) || (web(scheme) && web(parsed.scheme())));
+    match parsed.port() {
+        Some(port) if same_server => out.push_str(&format!(":{}", port)),
+        _ => {}
+    }
+    out.push_str(path);
+    if !remainder.is_empty() {
+        if !out.ends_with('/') {
+            out.push('/');
+        }
+        out.push_str(&remainder);
+    }
+    if let Some(query) = query {
+        out.push('?');
+        out.push_str(query);
+    }
+    if let Some(fragment) = parsed.fragment() {
+        out.push('#');
+        out.push_str(fragment);
+    }
+    Some(out)
+}
+
+/// Matches `from` against `url`, returning the captures and the unmatched rest of the path.
+fn match_from(from: &str, url: &Url) -> Option<(Vec<(String, String)>, String)> {
+    let (scheme, rest) = match from.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, from),
+    };
+    if scheme.is_some_and(|s| !s.eq_ignore_ascii_case(url.scheme())) {
+        return None;
+    }
+
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = normalize_host(host);
+    let url_host = normalize_host(url.host_str()?);
+    if host.is_empty() || (url_host != host && !url_host.ends_with(&format!(".{}", host))) {
+        return None;
+    }
+
+    let pattern: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let segments: Vec<&str> = url.path_segments().map(Iterator::collect)?;
+    if segments.len() < pattern.len() {
+        return None;
+    }
+
+    let mut captures = Vec::new();
+    for (want, got) in pattern.iter().zip(&segments) {
+        match want.strip_prefix('{').and_then(|w| w.strip_suffix('}')) {
+            Some(name) if !got.is_empty() => captures.push((name.to_string(), got.to_string())),
+            Some(_) => return None,
+            None if want == got => {}
+            None => return None,
+        }
+    }
+    Some((captures, segments[pattern.len()..].join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(from: &str, to: &str) -> RewriteRule {
+        RewriteRule {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_host_rewrite_keeps_rest() {
+        let r = rule("twitter.com", "xcancel.com");
+        assert_eq!(
+            rewrite(&r, "https://twitter.com/user/status/1?s=20#top").as_deref(),
+            Some("https://xcancel.com/user/status/1?s=20#top")
+        );
+        assert_eq!(
+            rewrite(&r, "https://mobile.twitter.com/user").as_deref(),
+            Some("https://xcancel.com/user")
+        );
+        assert_eq!(rewrite(&r, "https://nottwitter.com/user"), None);
+    }
+
+    #[test]
+    fn test_subdomain_rewrite() {
+        let r = rule("www.reddit.com", "old.reddit.com");
+        assert_eq!(
+            rewrite(&r, "https://www.reddit.com/r/rust/").as_deref(),
+            Some("https://old.reddit.com/r/rust/")
+        );
+        assert_eq!(rewrite(&r, "https://reddit.com/r/rust/"), None);
+    }
+
+    #[test]
+    fn test_path_captures_and_scheme() {
+        let r = rule("zoom.us/j/{id}", "zoommtg://zoom.us/join?confno={id}");
+        assert_eq!(
+            rewrite(&r, "https://us02web.zoom.us/j/123456?pwd=abc").as_deref(),
+            Some("zoommtg://zoom.us/join?confno=123456")
+        );
+        assert_eq!(rewrite(&r, "https://zoom.us/s/123456"), None);
+        assert_eq!(rewrite(&r, "https://zoom.us/j/"), None);
+
+        let r = rule(
+            "zoom.us/j/{id}",
+            "zoommtg://zoom.us/join?confno={id}&pwd={query.pwd}",
+        );
+        assert_eq!(
+            rewrite(&r, "https://zoom.us/j/42?pwd=abc").as_deref(),
+            Some("zoommtg://zoom.us/join?confno=42&pwd=abc")
+        );
+    }
+
+    #[test]
+    fn test_path_prefix_and_scheme_filter() {
+        let r = rule("http://example.com/old", "https://example.com/new");
+        assert_eq!(
+            rewrite(&r, "http://example.com/old/page?x=1").as_deref(),
+            Some("https://example.com/new/page?x=1")
+        );
+        assert_eq!(rewrite(&r, "https://example.com/old/page"), None);
+        assert_eq!(
+            rewrite(&r, "http://example.com:8080/old").as_deref(),
+            Some("https://example.com:8080/new")
+        );
+    }
+
+    #[test]
+    fn test_port_kept_only_for_same_host() {
+        let r = rule("localhost/x", "staging.example.com/x");
+        assert_eq!(
+            rewrite(&r, "http://localhost:3000/x/1").as_deref(),
+            Some("http://staging.example.com/x/1")
+        );
+
+        let r = rule("localhost/x", "localhost:4000/y");
+        assert_eq!(
+            rewrite(&r, "http://localhost:3000/x").as_deref(),
+            Some("http://localhost:4000/y")
+        );
+
+        let r = rule("zoom.us/j/{id}", "zoommtg://zoom.us/join?confno={id}");
+        assert_eq!(
+            rewrite(&r, "https://zoom.us:8443/j/123").as_deref(),
+            Some("zoommtg://zoom.us/join?confno=123")
+        );
+    }
+
+    #[test]
+    fn test_apply_section_then_global() {
+        let cfg: Config = toml::from_str(
+            r#"
+[[rewrite]]
+from = "nitter.net"
+to = "xcancel.com"
+
+[social]
+browser = "brave"
+url = ["twitter.com"]
+rewrite = [{ from = "twitter.com", to = "nitter.net" }]
+"#,
+        )
+        .unwrap();
+
+        let route = crate::router::route(&cfg, "https://twitter.com/user", None).unwrap();
+        assert_eq!(
+            apply(&cfg, &route, "https://twitter.com/user"),
+            "https://xcancel.com/user"
+        );
+        let route = crate::router::route(&cfg, "https://example.com", None).unwrap();
+        assert_eq!(
+            apply(&cfg, &route, "https://example.com"),
+            "https://example.com"
+        );
+    }
+}
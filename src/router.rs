@@ -0,0 +1,388 @@
+//! Routing decision for a single URL.
+//!
+//! This module combines client, host and scheme matching with the default
+//! browser so the same decision is made whether hyprchoosy runs in-process or
+//! as a daemon.
+
+use crate::config::{Action, Config, RewriteRule, RuleSection};
+use crate::matcher::{
+    is_excluded, match_client_for, match_host_for, match_scheme, parse_url_host, parse_url_scheme,
+};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "debug")]
+use log::info;
+
+/// Where a link goes: the matched browser and what to do with the link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route<'a> {
+    /// The matched rule section, or `None` for `[default]`.
+    pub section: Option<&'a str>,
+    pub browser: &'a str,
+    pub action: Action,
+    pub command: &'a [String],
+    pub rewrite: &'a [RewriteRule],
+}
+
+impl<'a> Route<'a> {
+    fn from_section(name: &'a str, sec: &'a RuleSection) -> Self {
+        Route {
+            section: Some(name),
+            browser: &sec.browser,
+            action: sec.action,
+            command: &sec.command,
+            rewrite: &sec.rewrite,
+        }
+    }
+
+    fn from_default(cfg: &'a Config) -> Self {
+        Route {
+            section: None,
+            browser: &cfg.default.browser,
+            action: cfg.default.action,
+            command: &cfg.default.command,
+            rewrite: &[],
+        }
+    }
+}
+
+/// Routing decisions forced from the command line, e.g. by `--section`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Overrides {
+    /// Use this section instead of matching rules; `"default"` is `[default]`.
+    #[serde(default)]
+    pub section: Option<String>,
+    /// Launch this browser, whatever the route's action was.
+    #[serde(default)]
+    pub browser: Option<String>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        self.section.is_none() && self.browser.is_none()
+    }
+}
+
+/// Routes `url`: client rules first, then host rules, then scheme-only
+/// rules, then the default. Only sections handling the link's scheme, and not
+/// excluding its host or client, match.
+pub fn route<'a>(cfg: &'a Config, url: &str, client: Option<&str>) -> Result<Route<'a>> {
+    let host = parse_url_host(url)?;
+    let scheme = parse_url_scheme(url)?;
+    #[cfg(feature = "debug")]
+    info!("Extracted host: '{}' (scheme '{}')", host, scheme);
+
+    // Sections whose `exclude_url` or `exclude_clients` rule this link out.
+    let candidates = || {
+        cfg.sections
+            .iter()
+            .filter(|(_, s)| !is_excluded(s, &host, client))
+    };
+
+    if let Some(c) = client {
+        #[cfg(feature = "debug")]
+        info!("Checking client rules for '{}'", c);
+
+        if let Some((name, sec)) = match_client_for(c, &scheme, candidates()) {
+            #[cfg(feature = "debug")]
+            info!("Using browser from client rule: '{}'", sec.browser);
+            return Ok(Route::from_section(name, sec));
+        }
+    } else {
+        #[cfg(feature = "debug")]
+        info!("No client detected, skipping client rules");
+    }
+
+    #[cfg(feature = "debug")]
+    info!("Checking host rules for '{}'", host);
+
+    let by_host = match_host_for(&host, &scheme, candidates()).or_else(|| {
+        // A bare host also matches `www.` patterns when `ignore_www` is on.
+        let bare = cfg.matching.ignore_www && !host.is_empty() && !host.starts_with("www.");
+        bare.then(|| match_host_for(&format!("www.{}", host), &scheme, candidates()))
+            .flatten()
+    });
+    if let Some((name, sec)) = by_host {
+        #[cfg(feature = "debug")]
+        info!("Using browser from host rule: '{}'", sec.browser);
+        return Ok(Route::from_section(name, sec));
+    }
+
+    if let Some((name, sec)) = match_scheme(&scheme, candidates()) {
+        #[cfg(feature = "debug")]
+        info!("Using browser from scheme rule: '{}'", sec.browser);
+        return Ok(Route::from_section(name, sec));
+    }
+
+    #[cfg(feature = "debug")]
+    info!(
+        "No rules matched, using default browser: '{}'",
+        cfg.default.browser
+    );
+    Ok(Route::from_default(cfg))
+}
+
+/// Like [`route`], but `overrides` take precedence over the rules.
+pub fn route_with<'a>(
+    cfg: &'a Config,
+    url: &str,
+    client: Option<&str>,
+    overrides: &'a Overrides,
+) -> Result<Route<'a>> {
+    let mut route = match overrides.section.as_deref() {
+        Some("default") => Route::from_default(cfg),
+        Some(name) => match cfg.sections.get_key_value(name) {
+            Some((name, sec)) => Route::from_section(name, sec),
+            None => bail!("No section named [{}] in the config", name),
+        },
+        None => route(cfg, url, client)?,
+    };
+
+    if let Some(browser) = &overrides.browser {
+        #[cfg(feature = "debug")]
+        info!("Forcing browser '{}'", browser);
+        route.browser = browser;
+        route.action = Action::Launch;
+    }
+    Ok(route)
+}
+
+/// Picks the browser for `url`, ignoring the section's action.
+pub fn choose_browser<'a>(cfg: &'a Config, url: &str, client: Option<&str>) -> Result<&'a str> {
+    Ok(route(cfg, url, client)?.browser)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = ["slack"]
+
+[dev]
+browser = "chromium"
+url = ["github.com"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_client_rule_wins_over_host() {
+        let cfg = config();
+        assert_eq!(
+            choose_browser(&cfg, "https://github.com", Some("slack")).unwrap(),
+            "chrome"
+        );
+    }
+
+    #[test]
+    fn test_host_rule() {
+        let cfg = config();
+        assert_eq!(
+            choose_browser(&cfg, "https://api.github.com", Some("discord")).unwrap(),
+            "chromium"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://github.com", None).unwrap(),
+            "chromium"
+        );
+    }
+
+    #[test]
+    fn test_default_browser() {
+        let cfg = config();
+        assert_eq!(
+            choose_browser(&cfg, "https://example.com", None).unwrap(),
+            "firefox"
+        );
+    }
+
+    #[test]
+    fn test_route_action() {
+        let mut cfg = config();
+        cfg.default.action = Action::Picker;
+
+        let unmatched = route(&cfg, "https://example.com", None).unwrap();
+        assert_eq!(unmatched.browser, "firefox");
+        assert_eq!(unmatched.action, Action::Picker);
+
+        let matched = route(&cfg, "https://github.com", None).unwrap();
+        assert_eq!(matched.action, Action::Launch);
+        assert_eq!(matched.section, Some("dev"));
+        assert_eq!(unmatched.section, None);
+    }
+
+    #[test]
+    fn test_schemes() {
+        let cfg: Config = toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+
+[work]
+browser = "chrome"
+clients = ["slack"]
+
+[work-mail]
+browser = "thunderbird"
+clients = ["slack"]
+schemes = ["mailto"]
+
+[mail]
+browser = "geary"
+schemes = ["mailto"]
+"#,
+        )
+        .unwrap();
+
+        let mailto = "mailto:bob@example.com";
+        assert_eq!(
+            choose_browser(&cfg, mailto, Some("slack")).unwrap(),
+            "thunderbird"
+        );
+        assert_eq!(
+            choose_browser(&cfg, mailto, Some("discord")).unwrap(),
+            "geary"
+        );
+        assert_eq!(route(&cfg, mailto, None).unwrap().section, Some("mail"));
+        assert_eq!(
+            choose_browser(&cfg, "https://example.com", Some("slack")).unwrap(),
+            "chrome"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "tel:+15551234", Some("slack")).unwrap(),
+            "firefox"
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let cfg = config();
+        let section = Overrides {
+            section: Some("work".to_string()),
+            ..Default::default()
+        };
+        let forced = route_with(&cfg, "https://github.com", None, &section).unwrap();
+        assert_eq!(forced.section, Some("work"));
+        assert_eq!(forced.browser, "chrome");
+
+        let default = Overrides {
+            section: Some("default".to_string()),
+            ..Default::default()
+        };
+        let forced = route_with(&cfg, "https://github.com", Some("slack"), &default).unwrap();
+        assert_eq!(forced.section, None);
+        assert_eq!(forced.browser, "firefox");
+
+        let missing = Overrides {
+            section: Some("nope".to_string()),
+            ..Default::default()
+        };
+        assert!(route_with(&cfg, "https://github.com", None, &missing).is_err());
+
+        let mut cfg = cfg;
+        cfg.sections.get_mut("dev").unwrap().action = Action::Picker;
+        let browser = Overrides {
+            browser: Some("brave".to_string()),
+            ..Default::default()
+        };
+        let forced = route_with(&cfg, "https://github.com", None, &browser).unwrap();
+        assert_eq!(forced.section, Some("dev"));
+        assert_eq!(forced.browser, "brave");
+        assert_eq!(forced.action, Action::Launch);
+    }
+
+    #[test]
+    fn test_ignore_www() {
+        let mut cfg: Config = toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+
+[news]
+browser = "brave"
+url = ["www.example.com"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            choose_browser(&cfg, "https://example.com", None).unwrap(),
+            "firefox"
+        );
+
+        cfg.matching.ignore_www = true;
+        assert_eq!(
+            choose_browser(&cfg, "https://example.com", None).unwrap(),
+            "brave"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://www.example.com/", None).unwrap(),
+            "brave"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://api.example.com", None).unwrap(),
+            "firefox"
+        );
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let cfg: Config = toml::from_str(
+            r#"
+[default]
+browser = "firefox"
+
+[google]
+browser = "chrome"
+url = ["google.com"]
+exclude_url = ["mail.google.com"]
+
+[mail]
+browser = "thunderbird"
+url = ["mail.google.com", "outlook.com"]
+
+[apps]
+browser = "chromium"
+url = ["example.com", "outlook.com"]
+exclude_clients = ["kitty"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            choose_browser(&cfg, "https://docs.google.com", None).unwrap(),
+            "chrome"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://mail.google.com/u/0", None).unwrap(),
+            "thunderbird"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://example.com", Some("slack")).unwrap(),
+            "chromium"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://example.com", Some("kitty")).unwrap(),
+            "firefox"
+        );
+        assert_eq!(
+            choose_browser(&cfg, "https://outlook.com", Some("kitty")).unwrap(),
+            "thunderbird"
+        );
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        let cfg = config();
+        assert!(choose_browser(&cfg, "not a url", None).is_err());
+    }
+}
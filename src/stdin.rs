@@ -0,0 +1,97 @@
+//! Reading links from standard input for `hyprchoosy -`.
+//!
+//! This module accepts either newline-separated URLs, as piped from newsboat
+//! or aerc, or a single JSON request for scripts that also want to name the
+//! client.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::io::Read;
+
+/// Links read from stdin and who they came from.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Input {
+    pub urls: Vec<String>,
+    /// Client to route for instead of the detected one.
+    pub client: Option<String>,
+}
+
+/// A JSON request, which may use `url` for a single link.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonRequest {
+    url: Option<String>,
+    #[serde(default)]
+    urls: Vec<String>,
+    client: Option<String>,
+}
+
+/// Parses stdin contents: a JSON object if it starts with `{`, otherwise one URL per line.
+///
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse(input: &str) -> Result<Input> {
+    let parsed = if input.trim_start().starts_with('{') {
+        let request: JsonRequest =
+            serde_json::from_str(input).context("Invalid JSON request on stdin")?;
+        Input {
+            urls: request.url.into_iter().chain(request.urls).collect(),
+            client: request.client,
+        }
+    } else {
+        Input {
+            urls: input
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            client: None,
+        }
+    };
+
+    if parsed.urls.is_empty() {
+        bail!("No URLs on stdin");
+    }
+    Ok(parsed)
+}
+
+/// Reads and parses all of stdin.
+pub fn read() -> Result<Input> {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Failed to read stdin")?;
+    parse(&input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let input = parse("https://a.com\n\n  https://b.com  \n# skipped\n").unwrap();
+        assert_eq!(input.urls, vec!["https://a.com", "https://b.com"]);
+        assert_eq!(input.client, None);
+    }
+
+    #[test]
+    fn test_json() {
+        let input = parse(r#"{"url": "https://a.com", "client": "aerc"}"#).unwrap();
+        assert_eq!(input.urls, vec!["https://a.com"]);
+        assert_eq!(input.client.as_deref(), Some("aerc"));
+
+        let input = parse(r#" {"urls": ["https://a.com", "https://b.com"]}"#).unwrap();
+        assert_eq!(input.urls.len(), 2);
+        assert_eq!(input.client, None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("\n# nothing\n").is_err());
+        assert!(parse(r#"{"client": "aerc"}"#).is_err());
+        assert!(parse(r#"{"link": "https://a.com"}"#).is_err());
+        assert!(parse("{not json").is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! Argument templating for `action = "command"`.
+//!
+//! This module fills `{url}`, `{host}`, `{path}` and `{query.<key>}`
+//! placeholders in a command's arguments from the link being opened.
+
+use crate::matcher::parse_url;
+use url::Url;
+
+/// Expands every argument of `argv` for `url`.
+pub fn expand_all(argv: &[String], url: &str) -> Vec<String> {
+    let parsed = parse_url(url).ok();
+    argv.iter()
+        .map(|arg| expand_with(arg, url, parsed.as_ref()))
+        .collect()
+}
+
+/// Expands the placeholders in `template` for `url`.
+///
+/// Parts the URL doesn't have expand to an empty string; unknown
+/// placeholders are left as they are.
+pub fn expand(template: &str, url: &str) -> String {
+    expand_with(template, url, parse_url(url).ok().as_ref())
+}
+
+fn expand_with(template: &str, url: &str, parsed: Option<&Url>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        if after[..end].contains('{') {
+            out.push('{');
+            rest = after;
+            continue;
+        }
+
+        match placeholder(&after[..end], url, parsed) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn placeholder(name: &str, url: &str, parsed: Option<&Url>) -> Option<String> {
+    let value = match name {
+        "url" => return Some(url.to_string()),
+        "host" => parsed.and_then(Url::host_str).map(str::to_lowercase),
+        "path" => parsed.map(|u| u.path().to_string()),
+        _ => {
+            let key = name.strip_prefix("query.")?;
+            parsed.and_then(|u| {
+                u.query_pairs()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.into_owned())
+            })
+        }
+    };
+    Some(value.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://Zoom.us/j/123?pwd=s%20ecret&uname=me";
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(expand("{url}", URL), URL);
+        assert_eq!(expand("{host}", URL), "zoom.us");
+        assert_eq!(expand("{path}", URL), "/j/123");
+        assert_eq!(expand("{query.pwd}", URL), "s ecret");
+        assert_eq!(
+            expand("zoommtg://{host}/join?confno=123&pwd={query.pwd}", URL),
+            "zoommtg://zoom.us/join?confno=123&pwd=s ecret"
+        );
+    }
+
+    #[test]
+    fn test_missing_and_unknown() {
+        assert_eq!(expand("[{query.missing}]", URL), "[]");
+        assert_eq!(expand("{nope} {url", URL), "{nope} {url");
+        assert_eq!(expand("{{host}}", URL), "{zoom.us}");
+        assert_eq!(expand("{host}", "not a url"), "");
+        assert_eq!(expand("{url}", "not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_expand_all() {
+        let argv = vec!["mpv".to_string(), "--".into(), "{url}".into()];
+        assert_eq!(
+            expand_all(&argv, "youtube.com/watch?v=x"),
+            vec!["mpv", "--", "youtube.com/watch?v=x"]
+        );
+    }
+}
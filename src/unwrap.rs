@@ -0,0 +1,147 @@
+//! Offline unwrapping of redirect and safe-link wrappers.
+//!
+//! Mail, chat and search apps wrap links in their own redirectors, so every
+//! link would route by the wrapper's host. This module pulls the real target
+//! out of the wrapper's query string without making any requests.
+
+use crate::config::UnwrapRule;
+use crate::matcher::parse_url;
+use url::Url;
+
+#[cfg(feature = "debug")]
+use log::debug;
+
+/// Known wrappers as `(host, path, param)`; hosts also match their subdomains.
+pub const BUILTIN_RULES: &[(&str, Option<&str>, &str)] = &[
+    ("safelinks.protection.outlook.com", None, "url"),
+    (
+        "statics.teams.cdn.office.net",
+        Some("/evergreen-assets/safelinks/1/atp-safelinks.html"),
+        "url",
+    ),
+    ("google.com", Some("/url"), "q"),
+    ("google.com", Some("/url"), "url"),
+    ("slack-redirect.slack.com", None, "url"),
+    ("l.facebook.com", Some("/l.php"), "u"),
+    ("lm.facebook.com", Some("/l.php"), "u"),
+    ("l.instagram.com", None, "u"),
+    ("youtube.com", Some("/redirect"), "q"),
+    ("linkedin.com", Some("/redir/redirect"), "url"),
+    ("steamcommunity.com", Some("/linkfilter/"), "url"),
+    ("vk.com", Some("/away.php"), "to"),
+];
+
+/// Wrappers nested deeper than this are left alone.
+const MAX_DEPTH: usize = 8;
+
+/// Returns the link `url` wraps, unwrapping nested wrappers, or `url` itself.
+///
+/// `extra` rules are tried before the built-in ones.
+pub fn unwrap_url(url: &str, extra: &[UnwrapRule]) -> String {
+    let mut current = url.to_string();
+    for _ in 0..MAX_DEPTH {
+        match unwrap_once(&current, extra) {
+            Some(target) => {
+                #[cfg(feature = "debug")]
+                debug!("Unwrapped '{}' to '{}'", current, target);
+                current = target;
+            }
+            None => break,
+        }
+    }
+    current
+}
+
+fn unwrap_once(url: &str, extra: &[UnwrapRule]) -> Option<String> {
+    let parsed = parse_url(url).ok()?;
+    let host = parsed.host_str()?.to_lowercase();
+
+    let extra = extra
+        .iter()
+        .map(|r| (r.host.as_str(), r.path.as_deref(), r.param.as_str()));
+    let mut rules = extra.chain(BUILTIN_RULES.iter().copied());
+
+    rules.find_map(|(rule_host, path, param)| {
+        let rule_host = rule_host.to_lowercase();
+        if host != rule_host && !host.ends_with(&format!(".{}", rule_host)) {
+            return None;
+        }
+        if path.is_some_and(|p| p != parsed.path()) {
+            return None;
+        }
+        let (_, target) = parsed.query_pairs().find(|(k, _)| k == param)?;
+        is_link(&target).then(|| target.into_owned())
+    })
+}
+
+/// Only absolute URLs with a host are taken as targets.
+fn is_link(target: &str) -> bool {
+    Url::parse(target).is_ok_and(|u| u.has_host())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_wrappers() {
+        let cases = [
+            (
+                "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fgithub.com%2Frust-lang&data=05%7C01",
+                "https://github.com/rust-lang",
+            ),
+            (
+                "https://www.google.com/url?sa=t&q=https://docs.rs/url&usg=abc",
+                "https://docs.rs/url",
+            ),
+            (
+                "https://slack-redirect.slack.com/link?url=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1",
+                "https://example.com/a?b=1",
+            ),
+            (
+                "https://l.facebook.com/l.php?u=https%3A%2F%2Fexample.org%2F&h=AT0",
+                "https://example.org/",
+            ),
+            (
+                "https://statics.teams.cdn.office.net/evergreen-assets/safelinks/1/atp-safelinks.html?url=https%3A%2F%2Fexample.com",
+                "https://example.com",
+            ),
+        ];
+        for (wrapped, target) in cases {
+            assert_eq!(unwrap_url(wrapped, &[]), target, "{}", wrapped);
+        }
+    }
+
+    #[test]
+    fn test_nested_and_untouched() {
+        let nested = "https://nam.safelinks.protection.outlook.com/?url=https%3A%2F%2Fwww.google.com%2Furl%3Fq%3Dhttps%253A%252F%252Fexample.com";
+        assert_eq!(unwrap_url(nested, &[]), "https://example.com");
+
+        for url in [
+            "https://www.google.com/search?q=https://example.com",
+            "https://www.google.com/url?q=not-a-link",
+            "https://l.facebook.com/l.php",
+            "https://github.com/?url=https://example.com",
+        ] {
+            assert_eq!(unwrap_url(url, &[]), url);
+        }
+    }
+
+    #[test]
+    fn test_extra_rules() {
+        let rules = vec![UnwrapRule {
+            host: "links.example.com".into(),
+            path: Some("/out".into()),
+            param: "target".into(),
+        }];
+        assert_eq!(
+            unwrap_url(
+                "https://links.example.com/out?target=https%3A%2F%2Fexample.org",
+                &rules
+            ),
+            "https://example.org"
+        );
+        let other_path = "https://links.example.com/in?target=https%3A%2F%2Fexample.org";
+        assert_eq!(unwrap_url(other_path, &rules), other_path);
+    }
+}
@@ -49,8 +49,9 @@ fn test_match_client_exact() {
         "work".to_string(),
         RuleSection {
             browser: "chrome".to_string(),
-            clients: vec!["slack".to_string()],
+            clients: vec!["slack".parse().unwrap()],
             url: vec![],
+            ..Default::default()
         },
     );
 
@@ -66,8 +67,9 @@ fn test_match_client_partial() {
         "work".to_string(),
         RuleSection {
             browser: "chrome".to_string(),
-            clients: vec!["slack".to_string()],
+            clients: vec!["slack".parse().unwrap()],
             url: vec![],
+            ..Default::default()
         },
     );
 
@@ -83,8 +85,9 @@ fn test_match_client_case_insensitive() {
         "work".to_string(),
         RuleSection {
             browser: "chrome".to_string(),
-            clients: vec!["Slack".to_string()],
+            clients: vec!["Slack".parse().unwrap()],
             url: vec![],
+            ..Default::default()
         },
     );
 
@@ -100,8 +103,9 @@ fn test_match_client_no_match() {
         "work".to_string(),
         RuleSection {
             browser: "chrome".to_string(),
-            clients: vec!["slack".to_string()],
+            clients: vec!["slack".parse().unwrap()],
             url: vec![],
+            ..Default::default()
         },
     );
 
@@ -118,6 +122,7 @@ fn test_match_host_exact() {
             browser: "firefox".to_string(),
             clients: vec![],
             url: vec!["github.com".to_string()],
+            ..Default::default()
         },
     );
 
@@ -135,6 +140,7 @@ fn test_match_host_subdomain() {
             browser: "firefox".to_string(),
             clients: vec![],
             url: vec!["github.com".to_string()],
+            ..Default::default()
         },
     );
 
@@ -156,6 +162,7 @@ fn test_match_host_case_insensitive() {
             browser: "firefox".to_string(),
             clients: vec![],
             url: vec!["GitHub.COM".to_string()],
+            ..Default::default()
         },
     );
 
@@ -173,6 +180,7 @@ fn test_match_host_no_match() {
             browser: "firefox".to_string(),
             clients: vec![],
             url: vec!["github.com".to_string()],
+            ..Default::default()
         },
     );
 
@@ -189,6 +197,7 @@ fn test_match_host_no_partial_match() {
             browser: "firefox".to_string(),
             clients: vec![],
             url: vec!["github.com".to_string()],
+            ..Default::default()
         },
     );
 
@@ -262,16 +271,18 @@ fn test_multiple_sections() {
         "work".to_string(),
         RuleSection {
             browser: "chrome".to_string(),
-            clients: vec!["slack".to_string()],
+            clients: vec!["slack".parse().unwrap()],
             url: vec![],
+            ..Default::default()
         },
     );
     sections.insert(
         "personal".to_string(),
         RuleSection {
             browser: "firefox".to_string(),
-            clients: vec!["discord".to_string()],
+            clients: vec!["discord".parse().unwrap()],
             url: vec![],
+            ..Default::default()
         },
     );
 